@@ -1,5 +1,5 @@
 use std::{ env, error::Error, process };
-use intel8080::*;
+use intel8080::{CPU, trace::{TraceFormat, TraceWriter}};
 
 fn main() {
     if let Err(e) = load_execute() {
@@ -28,11 +28,16 @@ fn load_execute() -> Result<(), Box<dyn Error>> {
     and does not set any stack. */
     c.sp = 0xFF00;
 
+    // Optional trace file, in the superzazu/8080 format (see the tracediff example)
+    if let Some(path) = a.get(2) { c.set_tracer(TraceWriter::file(path, TraceFormat::Superzazu)?) }
+
     loop {
         c.execute();
         if c.pc == 0x0005 { bdos_call(&c) }
         if c.pc == 0x0000 { break }             //  if CP/M warm boot -> we exit
     }
+    // Flushes the trace file
+    c.remove_tracer();
     Ok(())
 }
 
@@ -1,5 +1,5 @@
 use std::{ env, error::Error, process };
-use intel8080::*;
+use intel8080::{CPU, trace::{TraceFormat, TraceWriter}};
 
 fn main() {
     if let Err(e) = load_execute() {
@@ -11,7 +11,7 @@ fn main() {
 fn load_execute() -> Result<(), Box<dyn Error>> {
     let  a: Vec<String> = env::args().collect();
     let mut c = CPU::new();
-    c.debug.switch = true;
+    c.set_tracer(TraceWriter::new(std::io::stdout(), TraceFormat::Text));
 
     // Loads assembled program into memory
     c.bus.load_bin(&a[1], 0x000)?;
@@ -22,7 +22,6 @@ fn load_execute() -> Result<(), Box<dyn Error>> {
 
     loop {
         c.execute();
-        println!("{}\n", c.debug.string);
         if c.pc == 0x0000 { break }             //  if CP/M warm boot -> we exit
     }
     Ok(())
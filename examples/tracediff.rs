@@ -0,0 +1,28 @@
+// Reports the first divergence between two traces, for instance our trace and a reference emulator's one :
+// cargo run --example cpmloader -- program.com ours.txt
+// cargo run --example tracediff -- ours.txt reference.txt [context lines]
+use std::{ env, error::Error, fs::File, io::BufReader, process };
+use intel8080::tracediff::diff;
+
+fn main() {
+    match compare() {
+        Ok(true) => {},
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            println!("{}", e);
+            process::exit(2);
+        }
+    }
+}
+
+fn compare() -> Result<bool, Box<dyn Error>> {
+    let a: Vec<String> = env::args().collect();
+    if a.len() < 3 { return Err("usage : tracediff <left trace> <right trace> [context lines]".into()) }
+    let context = match a.get(3) { Some(n) => n.parse()?, None => 5 };
+    let left = BufReader::new(File::open(&a[1])?);
+    let right = BufReader::new(File::open(&a[2])?);
+    match diff(left, right, context)? {
+        Some(d) => { print!("{}", d); Ok(false) },
+        None => { println!("Traces are identical"); Ok(true) },
+    }
+}
@@ -0,0 +1,397 @@
+//! Conditional breakpoints.
+//!
+//! A condition is an expression over the registers (A, B, C, D, E, H, L, F, BC, DE, HL, SP, PC), the flags
+//! (S, Z, AC, P, CY), memory bytes ([HL], [$2000 + B]), the virtual clock (CYCLES) and the hit count of the
+//! breakpoint (HITS). Numbers are decimal, or hexadecimal with a $ or 0x prefix. Operators, by increasing
+//! precedence : `||`, `&&`, comparisons (`==` `!=` `<` `<=` `>` `>=`), `|`, `^`, `&`, `+` `-`, `*` `/` `%`,
+//! and the unary `!` `-`. A flag or a comparison is 1 when true, 0 when false, and an expression is true
+//! when it is not 0. Names are case insensitive.
+//! ```rust
+//! use intel8080::{CPU, breakpoint::Breakpoints};
+//! let mut c = CPU::new();
+//! c.bus.write_byte(0x0000, 0x3c);     // INR A
+//! c.bus.write_byte(0x0001, 0xc3);     // JMP $0000
+//! c.bus.write_word(0x0002, 0x0000);
+//! let mut b = Breakpoints::new();
+//! b.add(Some(0x0001), "A > 10 && !Z").unwrap();
+//! c.run_until(|c| b.check(c).is_some());
+//! assert_eq!((c.pc, c.reg.a), (0x0001, 11));
+//! assert_eq!(b.get(0).unwrap().hits, 11);
+//! ```
+use std::{fmt, str::FromStr};
+use crate::CPU;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register { A, B, C, D, E, H, L, F, BC, DE, HL, SP, PC }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flag { S, Z, AC, P, CY }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp { Or, And, Eq, Ne, Lt, Le, Gt, Ge, BitOr, BitXor, BitAnd, Add, Sub, Mul, Div, Rem }
+
+/// Parsed expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Number(i64),
+    Register(Register),
+    Flag(Flag),
+    Cycles,
+    Hits,
+    /// Byte at an address
+    Memory(Box<Expr>),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+/// Syntax error, at a byte offset of the expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    // Operators and brackets
+    Symbol(&'static str),
+    End,
+}
+
+const SYMBOLS: [&str; 21] = ["||", "&&", "==", "!=", "<=", ">=", "<", ">", "|", "^", "&", "+", "-", "*", "/", "%", "!", "(", ")", "[", "]"];
+
+// Splits the expression into tokens and their positions
+fn tokenize(s: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < s.len() {
+        let rest = &s[i..];
+        let c = rest.chars().next().unwrap_or_default();
+        if c.is_whitespace() { i += c.len_utf8(); continue }
+        let word: String = rest.chars().take_while(|c| c.is_ascii_alphanumeric() || *c == '$' || *c == '_').collect();
+        if c.is_ascii_digit() || c == '$' {
+            let n = match word.strip_prefix('$').or_else(|| word.strip_prefix("0x")).or_else(|| word.strip_prefix("0X")) {
+                Some(hex) => i64::from_str_radix(hex, 16),
+                None => word.parse(),
+            };
+            let n = n.map_err(|_| ParseError { position: i, message: format!("invalid number '{}'", word) })?;
+            tokens.push((Token::Number(n), i));
+            i += word.len();
+        } else if c.is_ascii_alphabetic() {
+            tokens.push((Token::Name(word.to_ascii_uppercase()), i));
+            i += word.len();
+        } else {
+            let symbol = SYMBOLS.iter().find(|s| rest.starts_with(**s))
+                .ok_or(ParseError { position: i, message: format!("unexpected character '{}'", c) })?;
+            tokens.push((Token::Symbol(symbol), i));
+            i += symbol.len();
+        }
+    }
+    tokens.push((Token::End, s.len()));
+    Ok(tokens)
+}
+
+// Binary operators, by increasing precedence
+const LEVELS: [&[(&str, BinOp)]; 8] = [
+    &[("||", BinOp::Or)],
+    &[("&&", BinOp::And)],
+    &[("==", BinOp::Eq), ("!=", BinOp::Ne), ("<=", BinOp::Le), (">=", BinOp::Ge), ("<", BinOp::Lt), (">", BinOp::Gt)],
+    &[("|", BinOp::BitOr)],
+    &[("^", BinOp::BitXor)],
+    &[("&", BinOp::BitAnd)],
+    &[("+", BinOp::Add), ("-", BinOp::Sub)],
+    &[("*", BinOp::Mul), ("/", BinOp::Div), ("%", BinOp::Rem)],
+];
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.next].0
+    }
+
+    fn error(&self, message: &str) -> ParseError {
+        ParseError { position: self.tokens[self.next].1, message: message.to_string() }
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), ParseError> {
+        if *self.peek() != Token::Symbol(SYMBOLS.iter().find(|s| **s == symbol).unwrap()) {
+            return Err(self.error(&format!("expected '{}'", symbol)))
+        }
+        self.next += 1;
+        Ok(())
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, ParseError> {
+        if level == LEVELS.len() { return self.unary() }
+        let mut left = self.binary(level + 1)?;
+        loop {
+            let op = match self.peek() {
+                Token::Symbol(s) => LEVELS[level].iter().find(|(o, _)| o == s).map(|(_, op)| *op),
+                _ => None,
+            };
+            let Some(op) = op else { return Ok(left) };
+            self.next += 1;
+            let right = self.binary(level + 1)?;
+            // Comparisons are not associative
+            if level == 2 && matches!(self.peek(), Token::Symbol(s) if LEVELS[2].iter().any(|(o, _)| o == s)) {
+                return Err(self.error("comparisons must be grouped with parentheses"))
+            }
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        let token = self.peek().clone();
+        self.next += 1;
+        Ok(match token {
+            Token::Symbol("!") => Expr::Not(Box::new(self.unary()?)),
+            Token::Symbol("-") => Expr::Neg(Box::new(self.unary()?)),
+            Token::Symbol("(") => {
+                let e = self.binary(0)?;
+                self.expect(")")?;
+                e
+            },
+            Token::Symbol("[") => {
+                let e = self.binary(0)?;
+                self.expect("]")?;
+                Expr::Memory(Box::new(e))
+            },
+            Token::Number(n) => Expr::Number(n),
+            Token::Name(name) => match name.as_str() {
+                "A" => Expr::Register(Register::A),
+                "B" => Expr::Register(Register::B),
+                "C" => Expr::Register(Register::C),
+                "D" => Expr::Register(Register::D),
+                "E" => Expr::Register(Register::E),
+                "H" => Expr::Register(Register::H),
+                "L" => Expr::Register(Register::L),
+                "F" => Expr::Register(Register::F),
+                "BC" => Expr::Register(Register::BC),
+                "DE" => Expr::Register(Register::DE),
+                "HL" => Expr::Register(Register::HL),
+                "SP" => Expr::Register(Register::SP),
+                "PC" => Expr::Register(Register::PC),
+                "S" => Expr::Flag(Flag::S),
+                "Z" => Expr::Flag(Flag::Z),
+                "AC" => Expr::Flag(Flag::AC),
+                "P" => Expr::Flag(Flag::P),
+                "CY" => Expr::Flag(Flag::CY),
+                "CYCLES" => Expr::Cycles,
+                "HITS" => Expr::Hits,
+                _ => { self.next -= 1; return Err(self.error(&format!("unknown name '{}'", name))) },
+            },
+            _ => { self.next -= 1; return Err(self.error("expected a value")) },
+        })
+    }
+}
+
+impl FromStr for Expr {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Expr, ParseError> {
+        Expr::parse(s)
+    }
+}
+
+impl Expr {
+    pub fn parse(s: &str) -> Result<Expr, ParseError> {
+        let mut p = Parser { tokens: tokenize(s)?, next: 0 };
+        let e = p.binary(0)?;
+        if *p.peek() != Token::End { return Err(p.error("unexpected token")) }
+        Ok(e)
+    }
+
+    /// Evaluates the expression. `hits` is the value of HITS. A division by 0 gives 0.
+    pub fn eval(&self, c: &CPU, hits: u64) -> i64 {
+        match self {
+            Expr::Number(n) => *n,
+            Expr::Register(r) => i64::from(match r {
+                Register::A => u16::from(c.reg.a),
+                Register::B => u16::from(c.reg.b),
+                Register::C => u16::from(c.reg.c),
+                Register::D => u16::from(c.reg.d),
+                Register::E => u16::from(c.reg.e),
+                Register::H => u16::from(c.reg.h),
+                Register::L => u16::from(c.reg.l),
+                Register::F => u16::from(c.flags.as_byte()),
+                Register::BC => c.reg.get_bc(),
+                Register::DE => c.reg.get_de(),
+                Register::HL => c.reg.get_hl(),
+                Register::SP => c.sp,
+                Register::PC => c.pc,
+            }),
+            Expr::Flag(f) => i64::from(match f {
+                Flag::S => c.flags.s,
+                Flag::Z => c.flags.z,
+                Flag::AC => c.flags.a,
+                Flag::P => c.flags.p,
+                Flag::CY => c.flags.c,
+            }),
+            Expr::Cycles => c.cycles() as i64,
+            Expr::Hits => hits as i64,
+            Expr::Memory(a) => i64::from(c.bus.read_byte(a.eval(c, hits) as u16)),
+            Expr::Not(e) => i64::from(e.eval(c, hits) == 0),
+            Expr::Neg(e) => e.eval(c, hits).wrapping_neg(),
+            Expr::Binary(op, l, r) => {
+                let l = l.eval(c, hits);
+                // && and || only evaluate the right operand when needed
+                match op {
+                    BinOp::And => return i64::from(l != 0 && r.eval(c, hits) != 0),
+                    BinOp::Or => return i64::from(l != 0 || r.eval(c, hits) != 0),
+                    _ => {}
+                }
+                let r = r.eval(c, hits);
+                match op {
+                    BinOp::Eq => i64::from(l == r),
+                    BinOp::Ne => i64::from(l != r),
+                    BinOp::Lt => i64::from(l < r),
+                    BinOp::Le => i64::from(l <= r),
+                    BinOp::Gt => i64::from(l > r),
+                    BinOp::Ge => i64::from(l >= r),
+                    BinOp::BitOr => l | r,
+                    BinOp::BitXor => l ^ r,
+                    BinOp::BitAnd => l & r,
+                    BinOp::Add => l.wrapping_add(r),
+                    BinOp::Sub => l.wrapping_sub(r),
+                    BinOp::Mul => l.wrapping_mul(r),
+                    BinOp::Div => l.checked_div(r).unwrap_or(0),
+                    BinOp::Rem => l.checked_rem(r).unwrap_or(0),
+                    BinOp::And | BinOp::Or => unreachable!(),
+                }
+            },
+        }
+    }
+}
+
+/// A breakpoint : an address, a condition, or both.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    /// None : the condition is checked before every instruction
+    pub address: Option<u16>,
+    /// Source of the condition, empty if there is none
+    pub source: String,
+    pub condition: Option<Expr>,
+    /// Times the address has been reached (every check if there is no address), whether the condition was true or not
+    pub hits: u64,
+    pub enabled: bool,
+}
+
+/// A set of breakpoints, identified by the number returned by add().
+#[derive(Debug, Clone, Default)]
+pub struct Breakpoints {
+    list: Vec<Option<Breakpoint>>,
+}
+
+impl Breakpoints {
+    pub fn new() -> Breakpoints {
+        Breakpoints::default()
+    }
+
+    /// Adds a breakpoint, and returns its number. An empty condition is always true.
+    pub fn add(&mut self, address: Option<u16>, condition: &str) -> Result<usize, ParseError> {
+        let expr = if condition.trim().is_empty() { None } else { Some(Expr::parse(condition)?) };
+        self.list.push(Some(Breakpoint { address, source: condition.trim().to_string(), condition: expr, hits: 0, enabled: true }));
+        Ok(self.list.len() - 1)
+    }
+
+    /// Removes a breakpoint. Returns false if there is no such breakpoint.
+    pub fn remove(&mut self, n: usize) -> bool {
+        self.list.get_mut(n).and_then(|b| b.take()).is_some()
+    }
+
+    pub fn get(&self, n: usize) -> Option<&Breakpoint> {
+        self.list.get(n).and_then(|b| b.as_ref())
+    }
+
+    pub fn get_mut(&mut self, n: usize) -> Option<&mut Breakpoint> {
+        self.list.get_mut(n).and_then(|b| b.as_mut())
+    }
+
+    /// Breakpoints and their numbers.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &Breakpoint)> {
+        self.list.iter().enumerate().filter_map(|(n, b)| b.as_ref().map(|b| (n, b)))
+    }
+
+    /// To be called before each instruction : counts the hits of the enabled breakpoints at pc (and of
+    /// those without address), and returns the number of the first one whose condition is true.
+    pub fn check(&mut self, c: &CPU) -> Option<usize> {
+        let mut stop = None;
+        for (n, b) in self.list.iter_mut().enumerate() {
+            let Some(b) = b.as_mut().filter(|b| b.enabled && b.address.is_none_or(|a| a == c.pc)) else { continue };
+            b.hits += 1;
+            if b.condition.as_ref().is_none_or(|e| e.eval(c, b.hits) != 0) { stop = stop.or(Some(n)) }
+        }
+        stop
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::cpu;
+
+    fn eval(s: &str, c: &CPU) -> i64 {
+        Expr::parse(s).unwrap().eval(c, 3)
+    }
+
+    #[test]
+    fn expressions() {
+        let mut c = cpu(&[(0x2000, &[0x0d, 0x42])]);
+        c.reg.set_hl(0x2000);
+        c.reg.a = 12;
+        c.flags.z = true;
+        assert_eq!(eval("[HL] == 0x0D", &c), 1);
+        assert_eq!(eval("[hl + 1]", &c), 0x42);
+        assert_eq!(eval("A > 10 && Z", &c), 1);
+        assert_eq!(eval("A > 10 && !Z || CY", &c), 0);
+        assert_eq!(eval("1 + 2 * 3 - -4", &c), 11);
+        assert_eq!(eval("(1 + 2) * 3 % 5", &c), 4);
+        assert_eq!(eval("$f0 & 0x3c | 1 ^ 3", &c), 0x32);
+        assert_eq!(eval("HL / 0 + F", &c), 0x42);
+        assert_eq!(eval("hits == 3 && cycles < 1", &c), 1);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(Expr::parse("A > "), Err(ParseError { position: 4, message: String::from("expected a value") }));
+        assert_eq!(Expr::parse("[HL == 1").unwrap_err().to_string(), "expected ']' at position 8");
+        assert_eq!(Expr::parse("Q == 1").unwrap_err().message, "unknown name 'Q'");
+        assert_eq!(Expr::parse("A @ 1").unwrap_err().position, 2);
+        assert_eq!(Expr::parse("$fg").unwrap_err().message, "invalid number '$fg'");
+        assert_eq!(Expr::parse("1 < A < 3").unwrap_err().position, 6);
+        assert_eq!(Expr::parse("A 1").unwrap_err().message, "unexpected token");
+        assert!("[HL]".parse::<Expr>().is_ok());
+    }
+
+    #[test]
+    fn breakpoints() {
+        // $0000 : INR A / JMP $0000
+        let mut c = cpu(&[(0x0000, &[0x3c, 0xc3, 0x00, 0x00])]);
+        let mut b = Breakpoints::new();
+        let every = b.add(Some(0x0000), "hits % 4 == 0").unwrap();
+        let cycles = b.add(None, "CYCLES >= 100").unwrap();
+        c.run_until(|c| b.check(c).is_some());
+        assert_eq!((c.pc, c.reg.a), (0x0000, 4));
+        b.get_mut(every).unwrap().enabled = false;
+        c.run_until(|c| b.check(c).is_some());
+        assert_eq!(c.cycles(), 105);
+        assert!(b.remove(cycles));
+        assert!(!b.remove(cycles));
+        assert_eq!(b.iter().map(|(n, b)| (n, b.source.as_str(), b.hits)).collect::<Vec<_>>(), vec![(0, "hits % 4 == 0", 4)]);
+    }
+}
@@ -0,0 +1,212 @@
+//! Shadow call stack, for backtraces.
+//!
+//! CallStack is a trace sink. A frame is pushed by each CALL, RST or interrupt which pushes a return address,
+//! and popped by the return to this address. Programs which manipulate the stack pointer are tolerated : the
+//! frames whose return address is below the stack pointer are dropped. A return which does not go back to
+//! the address pushed by the matching call (computed jump with PUSH / RET, modified return address...) is
+//! recorded as a mismatch.
+//! ```rust
+//! use intel8080::{CPU, callstack::CallStack};
+//! use std::{cell::RefCell, rc::Rc};
+//! let mut c = CPU::new();
+//! c.sp = 0xff00;
+//! c.bus.write_byte(0x0000, 0xcd);     // CALL $0010
+//! c.bus.write_word(0x0001, 0x0010);
+//! c.bus.write_byte(0x0010, 0xcf);     // RST 1
+//! let s = Rc::new(RefCell::new(CallStack::new()));
+//! s.borrow_mut().add_symbol(0x0010, "print");
+//! c.set_tracer(s.clone());
+//! c.execute();
+//! c.execute();
+//! assert_eq!(s.borrow().backtrace(), "#0  $0008 in $0008\n#1  $0011 in print\n#2  $0003 in root\n");
+//! ```
+use std::collections::HashMap;
+use crate::{run::{above, is_call, is_ret}, trace::{TraceEvent, TraceSink}};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallKind {
+    Call,
+    Rst,
+    Interrupt,
+}
+
+/// A subroutine being executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    pub kind: CallKind,
+    /// Address of the call instruction, or of the interrupted instruction
+    pub caller: u16,
+    /// Entry point of the subroutine
+    pub entry: u16,
+    pub return_address: u16,
+    /// Stack pointer once the return address has been pushed
+    pub sp: u16,
+}
+
+/// A return which did not go back to the address pushed by the matching call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mismatch {
+    /// Address of the return instruction
+    pub address: u16,
+    /// Address returned to
+    pub target: u16,
+    /// Return address pushed by the call, None if the popped address was not pushed by a call
+    pub expected: Option<u16>,
+}
+
+#[derive(Default)]
+pub struct CallStack {
+    frames: Vec<Frame>,
+    mismatches: Vec<Mismatch>,
+    symbols: HashMap<u16, String>,
+    // Current instruction : address, opcode, supplied by an interrupting device
+    current: Option<(u16, u8, bool)>,
+    interrupt: bool,
+    // Registers before the current instruction
+    pc: u16,
+    sp: Option<u16>,
+}
+
+impl CallStack {
+    pub fn new() -> CallStack {
+        CallStack::default()
+    }
+
+    /// Names a subroutine in the backtraces.
+    pub fn add_symbol(&mut self, address: u16, name: &str) {
+        self.symbols.insert(address, name.to_string());
+    }
+
+    /// Forgets the frames and the mismatches. Symbols are kept.
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.mismatches.clear();
+    }
+
+    /// Frames, the innermost last.
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn mismatches(&self) -> &[Mismatch] {
+        &self.mismatches
+    }
+
+    fn name(&self, entry: Option<u16>) -> String {
+        match entry {
+            None => String::from("root"),
+            Some(a) => self.symbols.get(&a).cloned().unwrap_or_else(|| format!("${:04x}", a)),
+        }
+    }
+
+    /// Current address, then the return address of each frame, with the subroutine they belong to.
+    pub fn backtrace(&self) -> String {
+        let mut s = format!("#0  ${:04x} in {}\n", self.pc, self.name(self.frames.last().map(|f| f.entry)));
+        for (n, f) in self.frames.iter().enumerate().rev() {
+            let entry = n.checked_sub(1).map(|n| self.frames[n].entry);
+            let interrupt = if f.kind == CallKind::Interrupt { " (interrupted)" } else { "" };
+            s += &format!("#{}  ${:04x} in {}{}\n", self.frames.len() - n, f.return_address, self.name(entry), interrupt);
+        }
+        s
+    }
+
+    // Follows the calls and returns once the registers after the instruction are known
+    fn registers(&mut self, pc: u16, sp: u16, stack: u16) {
+        if let (Some((address, opcode, interrupt)), Some(before)) = (self.current.take(), self.sp) {
+            if is_call(opcode) && sp == before.wrapping_sub(2) {
+                let kind = if interrupt { CallKind::Interrupt } else if opcode & 0xC7 == 0xC7 { CallKind::Rst } else { CallKind::Call };
+                self.frames.push(Frame { kind, caller: address, entry: pc, return_address: stack, sp });
+            } else if is_ret(opcode) && sp == before.wrapping_add(2) {
+                while self.frames.last().is_some_and(|f| above(before, f.sp)) { self.frames.pop(); }
+                let expected = match self.frames.last() {
+                    Some(f) if f.sp == before => self.frames.pop().map(|f| f.return_address),
+                    _ => None,
+                };
+                if expected != Some(pc) { self.mismatches.push(Mismatch { address, target: pc, expected }) }
+            }
+        }
+        // Stack pointer moved above the return address
+        while self.frames.last().is_some_and(|f| above(sp, f.sp)) { self.frames.pop(); }
+        self.pc = pc;
+        self.sp = Some(sp);
+    }
+}
+
+impl TraceSink for CallStack {
+    fn event(&mut self, event: &TraceEvent) {
+        match *event {
+            TraceEvent::Interrupt { .. } => self.interrupt = true,
+            TraceEvent::Instruction { address, bytes, .. } => self.current = Some((address, bytes[0], std::mem::take(&mut self.interrupt))),
+            TraceEvent::Registers(r) => self.registers(r.pc, r.sp, r.stack),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{attach, cpu};
+
+    #[test]
+    fn calls_and_interrupt() {
+        // $0000 : CALL $0010 / HLT, $0010 : CALL $0020 / RET, $0020 : JMP $0020, $0038 : RET
+        let mut c = cpu(&[(0x0000, &[0xcd, 0x10, 0x00, 0x76]), (0x0010, &[0xcd, 0x20, 0x00, 0xc9]),
+            (0x0020, &[0xc3, 0x20, 0x00]), (0x0038, &[0xc9])]);
+        let s = attach(&mut c, CallStack::new());
+        s.borrow_mut().add_symbol(0x0020, "wait");
+        c.run_until(|c| c.pc == 0x0020);
+        c.inte = true;
+        c.int = (true, 0xff);                           // RST 7
+        c.execute();
+        assert_eq!(s.borrow().backtrace(), "#0  $0038 in $0038\n#1  $0020 in wait (interrupted)\n#2  $0013 in $0010\n#3  $0003 in root\n");
+        assert_eq!(s.borrow().frames()[2], Frame { kind: CallKind::Interrupt, caller: 0x0020, entry: 0x0038, return_address: 0x0020, sp: 0xfefa });
+        c.execute();
+        assert_eq!(s.borrow().depth(), 2);
+        assert!(s.borrow().mismatches().is_empty());
+    }
+
+    #[test]
+    fn mismatches() {
+        // $0000 : CALL $0010 / LXI H,$0020 / PUSH H / RET
+        // $0010 : XTHL / INX H / XTHL / RET (skips the byte after the call)
+        let mut c = cpu(&[(0x0000, &[0xcd, 0x10, 0x00, 0x00, 0x21, 0x20, 0x00, 0xe5, 0xc9]),
+            (0x0010, &[0xe3, 0x23, 0xe3, 0xc9]), (0x0020, &[0x76])]);
+        let s = attach(&mut c, CallStack::new());
+        c.run_until(|c| c.halt);
+        assert_eq!(s.borrow().mismatches(), &[
+            Mismatch { address: 0x0013, target: 0x0004, expected: Some(0x0003) },
+            Mismatch { address: 0x0008, target: 0x0020, expected: None },
+        ]);
+        assert_eq!(s.borrow().depth(), 0);
+    }
+
+    #[test]
+    fn wrapped_stack() {
+        // $0000 : CALL $0010 / CALL $0010 / HLT, $0010 : CALL $0020 / RET, $0020 : RET
+        let mut c = cpu(&[(0x0000, &[0xcd, 0x10, 0x00, 0xcd, 0x10, 0x00, 0x76]), (0x0010, &[0xcd, 0x20, 0x00, 0xc9]), (0x0020, &[0xc9])]);
+        c.sp = 0x0002;
+        let s = attach(&mut c, CallStack::new());
+        c.run_until(|c| c.pc == 0x0020);
+        assert_eq!(s.borrow().frames().iter().map(|f| f.sp).collect::<Vec<_>>(), vec![0x0000, 0xfffe]);
+        c.run_until(|c| c.halt);
+        assert_eq!(s.borrow().depth(), 0);
+        assert!(s.borrow().mismatches().is_empty());
+    }
+
+    #[test]
+    fn stack_manipulation() {
+        // $0000 : CALL $0010, $0010 : CALL $0020, $0020 : LXI SP,$ff00 / JMP $0000
+        let mut c = cpu(&[(0x0000, &[0xcd, 0x10, 0x00]), (0x0010, &[0xcd, 0x20, 0x00]), (0x0020, &[0x31, 0x00, 0xff, 0xc3, 0x00, 0x00])]);
+        let s = attach(&mut c, CallStack::new());
+        c.run_until(|c| c.pc == 0x0023);
+        assert_eq!(s.borrow().depth(), 0);
+        c.run_until(|c| c.pc == 0x0020);
+        assert_eq!(s.borrow().depth(), 2);
+        assert!(s.borrow().mismatches().is_empty());
+    }
+}
@@ -0,0 +1,249 @@
+//! Code coverage : executed instructions and conditional branches taken / not taken.
+//!
+//! Coverage is a trace sink. The instructions supplied by interrupting devices are not counted.
+//! Reports are an annotated disassembly and an lcov tracefile, whose lines come from an assembler
+//! listing, or from the annotated disassembly itself.
+//! ```rust
+//! use intel8080::{CPU, coverage::{Coverage, Listing}};
+//! use std::{cell::RefCell, rc::Rc};
+//! let mut c = CPU::new();
+//! c.bus.write_byte(0x0000, 0xca);     // JZ $0000
+//! c.bus.write_word(0x0001, 0x0000);
+//! c.bus.write_byte(0x0003, 0x76);     // HLT
+//! let cov = Rc::new(RefCell::new(Coverage::new()));
+//! c.set_tracer(cov.clone());
+//! c.run_until(|c| c.halt);
+//! let dis = cov.borrow().annotate(&c.bus, 0x0000..=0x0004);
+//! assert_eq!(dis, "0000  CA 0000   JZ $0000           ; 1 branch taken 0 not taken 1\n\
+//!                  0003  76        HLT                ; 1\n\
+//!                  0004  00        NOP                ; -\n");
+//! let lcov = cov.borrow().lcov(&c.bus, &Listing::parse("program.dis", &dis));
+//! assert!(lcov.contains("DA:2,1\nDA:3,0\n"));
+//! ```
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use crate::{dasm::disassemble_or_data, length, memory::Bus, trace::{TraceEvent, TraceSink}};
+
+/// Executions of a conditional jump, call or return.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Branch {
+    pub taken: u64,
+    pub not_taken: u64,
+}
+
+// Jcc, Ccc, Rcc
+fn conditional(opcode: u8) -> bool {
+    matches!(opcode & 0xC7, 0xC0 | 0xC2 | 0xC4)
+}
+
+pub struct Coverage {
+    // Executions of the opcode at each address
+    counts: Vec<u64>,
+    branches: HashMap<u16, Branch>,
+    // Current instruction, waiting for the next pc
+    current: Option<(u16, [u8; 3])>,
+    interrupt: bool,
+    // Stack pointer before the current instruction
+    sp: u16,
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage { counts: vec![0; 0x10000], branches: HashMap::new(), current: None, interrupt: false, sp: 0 }
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.iter_mut().for_each(|c| *c = 0);
+        self.branches.clear();
+    }
+
+    /// Number of times an instruction starting at this address has been executed.
+    pub fn executed(&self, address: u16) -> u64 {
+        self.counts[usize::from(address)]
+    }
+
+    /// Returns the executions of a conditional instruction.
+    pub fn branch(&self, address: u16) -> Option<Branch> {
+        self.branches.get(&address).copied()
+    }
+
+    /// Disassembles memory, with the executions of each instruction ("-" if never executed) and of each branch.
+    pub fn annotate(&self, bus: &Bus, range: RangeInclusive<u16>) -> String {
+        let mut s = String::new();
+        let mut a = u32::from(*range.start());
+        while a <= u32::from(*range.end()) {
+            let address = a as u16;
+            let bytes = [0, 1, 2].map(|n| bus.read_byte(address.wrapping_add(n)));
+            let text = disassemble_or_data(bytes);
+            let mut info = match self.executed(address) { 0 => String::from("-"), n => n.to_string() };
+            if let Some(b) = self.branch(address) {
+                info += &format!(" branch taken {} not taken {}", b.taken, b.not_taken);
+            }
+            s += &format!("{:04X}  {:<28} ; {}\n", address, text, info);
+            a += u32::from(length(bytes[0]));
+        }
+        s
+    }
+
+    /// lcov tracefile : a line is covered if one of its instructions has been executed. Each conditional
+    /// instruction of a line is a block, numbered from 0, whose branches are 0 (taken) and 1 (not taken).
+    /// The conditional instructions are found in memory : the branches of those never executed are reported as "-".
+    pub fn lcov(&self, bus: &Bus, listing: &Listing) -> String {
+        let mut lines: Vec<(u32, u64, Vec<Option<Branch>>)> = Vec::new();
+        let mut sorted: Vec<_> = listing.lines.iter().collect();
+        sorted.sort_by_key(|(a, l)| (**l, **a));
+        for (address, line) in sorted {
+            let count = self.executed(*address);
+            let branch = conditional(bus.read_byte(*address)).then(|| self.branch(*address));
+            match lines.last_mut() {
+                Some(l) if l.0 == *line => { l.1 = l.1.max(count); l.2.extend(branch) },
+                _ => lines.push((*line, count, branch.into_iter().collect())),
+            }
+        }
+        let mut s = format!("TN:\nSF:{}\n", listing.file);
+        let (mut brf, mut brh) = (0, 0);
+        for (line, _, branches) in &lines {
+            for (n, b) in branches.iter().enumerate() {
+                match b {
+                    Some(b) => {
+                        s += &format!("BRDA:{},{},0,{}\nBRDA:{},{},1,{}\n", line, n, b.taken, line, n, b.not_taken);
+                        brh += u32::from(b.taken > 0) + u32::from(b.not_taken > 0);
+                    },
+                    None => s += &format!("BRDA:{},{},0,-\nBRDA:{},{},1,-\n", line, n, line, n),
+                }
+                brf += 2;
+            }
+        }
+        if brf > 0 { s += &format!("BRF:{}\nBRH:{}\n", brf, brh) }
+        for (line, count, _) in &lines {
+            s += &format!("DA:{},{}\n", line, count);
+        }
+        s += &format!("LF:{}\nLH:{}\nend_of_record\n", lines.len(), lines.iter().filter(|l| l.1 > 0).count());
+        s
+    }
+}
+
+impl TraceSink for Coverage {
+    fn event(&mut self, event: &TraceEvent) {
+        match *event {
+            TraceEvent::Interrupt { .. } => self.interrupt = true,
+            TraceEvent::Instruction { address, bytes, .. } if !std::mem::take(&mut self.interrupt) => {
+                self.counts[usize::from(address)] += 1;
+                self.current = Some((address, bytes));
+            },
+            TraceEvent::Registers(r) => {
+                if let Some((address, bytes)) = self.current.take().filter(|(_, b)| conditional(b[0])) {
+                    // A return may go back to the next instruction : it is taken if the stack pointer moved
+                    let taken = if bytes[0] & 0xC7 == 0xC0 { r.sp == self.sp.wrapping_add(2) } else { r.pc != address.wrapping_add(length(bytes[0])) };
+                    let b = self.branches.entry(address).or_default();
+                    if taken { b.taken += 1 } else { b.not_taken += 1 }
+                }
+                self.sp = r.sp;
+            },
+            _ => {}
+        }
+    }
+}
+
+/// Maps the addresses of a program to the lines of its source.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Listing {
+    /// Source file name, written to the lcov report
+    pub file: String,
+    /// Line number of each instruction address
+    pub lines: HashMap<u16, u32>,
+}
+
+fn hex_address(token: &str) -> Option<u16> {
+    let t = token.trim_start_matches('$').trim_end_matches(':').trim_end_matches('h');
+    if t.len() == 4 { u16::from_str_radix(t, 16).ok() } else { None }
+}
+
+impl Listing {
+    /// Parses an assembler listing, where lines with code start with the address (4 hex digits) followed by the bytes.
+    /// If the address is preceded by a decimal line number, the line number is used, otherwise the line of the listing itself.
+    /// ```text
+    /// 0100  31 00 FF      LXI SP,$FF00        (line of the listing)
+    ///   12  0100  31 00 FF      LXI SP,$FF00  (source line 12)
+    /// ```
+    pub fn parse(file: &str, listing: &str) -> Listing {
+        let mut lines = HashMap::new();
+        for (n, text) in listing.lines().enumerate() {
+            let mut tokens = text.split_whitespace();
+            let (first, second) = (tokens.next(), tokens.next());
+            let (address, line) = match (first, second) {
+                (Some(l), Some(a)) if l.chars().all(|c| c.is_ascii_digit()) && hex_address(a).is_some() => (hex_address(a), l.parse().ok()),
+                (Some(a), Some(b)) if u8::from_str_radix(b, 16).is_ok() && b.len() == 2 => (hex_address(a), Some(n as u32 + 1)),
+                _ => (None, None),
+            };
+            if let (Some(a), Some(l)) = (address, line) { lines.entry(a).or_insert(l); }
+        }
+        Listing { file: file.to_string(), lines }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{attach, cpu};
+
+    // $0000 : MVI B,$02 / DCR B / JNZ $0002 / RZ / HLT
+    const PROGRAM: &[(u16, &[u8])] = &[(0x0000, &[0x06, 0x02, 0x05, 0xc2, 0x02, 0x00, 0xc8, 0x76]), (0xff00, &[0x07, 0x00])];
+
+    #[test]
+    fn branches() {
+        let mut c = cpu(PROGRAM);
+        let cov = attach(&mut c, Coverage::new());
+        c.run_until(|c| c.halt);
+        let cov = cov.borrow();
+        assert_eq!(cov.executed(0x0002), 2);
+        assert_eq!(cov.branch(0x0003), Some(Branch { taken: 1, not_taken: 1 }));
+        // RZ taken : returns to $0007
+        assert_eq!(cov.branch(0x0006), Some(Branch { taken: 1, not_taken: 0 }));
+        assert_eq!(cov.branch(0x0002), None);
+        assert_eq!(cov.executed(0x0007), 1);
+    }
+
+    #[test]
+    fn interrupt() {
+        let mut c = cpu(PROGRAM);
+        let cov = attach(&mut c, Coverage::new());
+        c.inte = true;
+        c.int = (true, 0xc7);                           // RST 0
+        c.execute();
+        assert_eq!(cov.borrow().executed(0x0000), 0);
+    }
+
+    #[test]
+    fn listing() {
+        let l = Listing::parse("loop.asm", "; loop\n0000  06 02         MVI B,2\n   3  0002  05      DCR B\n     ORG $0100\n");
+        assert_eq!(l.lines, HashMap::from([(0x0000, 2), (0x0002, 3)]));
+        let mut c = cpu(PROGRAM);
+        let cov = attach(&mut c, Coverage::new());
+        c.execute();
+        assert_eq!(cov.borrow().lcov(&c.bus, &l), "TN:\nSF:loop.asm\nDA:2,1\nDA:3,0\nLF:2\nLH:1\nend_of_record\n");
+    }
+
+    #[test]
+    fn lcov_branches() {
+        let mut c = cpu(PROGRAM);
+        let cov = attach(&mut c, Coverage::new());
+        c.run_until(|c| c.halt);
+        let dis = cov.borrow().annotate(&c.bus, 0x0000..=0x0007);
+        assert_eq!(dis.lines().nth(2).unwrap(), "0003  C2 0200   JNZ $0002          ; 2 branch taken 1 not taken 1");
+        let lcov = cov.borrow().lcov(&c.bus, &Listing::parse("program.dis", &dis));
+        assert!(lcov.starts_with("TN:\nSF:program.dis\nBRDA:3,0,0,1\nBRDA:3,0,1,1\nBRDA:4,0,0,1\nBRDA:4,0,1,0\nBRF:4\nBRH:3\nDA:1,1\n"));
+        assert!(lcov.ends_with("LF:5\nLH:5\nend_of_record\n"));
+        // JNZ and RZ on the same source line, JC $0000 never executed
+        c.bus.write_byte(0x0010, 0xda);
+        let l = Listing { file: String::from("loop.asm"), lines: HashMap::from([(0x0000, 1), (0x0002, 2), (0x0003, 2), (0x0006, 2), (0x0010, 3)]) };
+        let lcov = cov.borrow().lcov(&c.bus, &l);
+        assert!(lcov.starts_with("TN:\nSF:loop.asm\nBRDA:2,0,0,1\nBRDA:2,0,1,1\nBRDA:2,1,0,1\nBRDA:2,1,1,0\nBRDA:3,0,0,-\nBRDA:3,0,1,-\nBRF:6\nBRH:3\n"));
+    }
+}
@@ -0,0 +1,141 @@
+//! HOLD / HLDA : direct memory access by bus master devices (floppy disk controllers, video boards).
+//!
+//! Between two instructions, the CPU grants the bus to each device asserting HOLD. The device performs its
+//! transfers directly on the bus, then releases it : the CPU is stalled meanwhile, and the stall is added
+//! to the cycles returned by execute() and to the virtual clock. The bytes written are sent to the trace
+//! sink as DmaWrite bus accesses, before the instruction.
+//!
+//! Unlike the hardware, which grants HOLD at the end of any machine cycle, HOLD is only granted between
+//! two instructions : a transfer may start up to an instruction later (18 clock cycles at most).
+//! ```rust
+//! use intel8080::{CPU, dma::DmaDevice, memory::Bus};
+//!
+//! // Copies a 4 bytes sector to $2000 once
+//! struct Disk { pending: bool }
+//!
+//! impl DmaDevice for Disk {
+//!     fn hold(&self) -> bool { self.pending }
+//!     fn transfer(&mut self, bus: &mut Bus) -> u32 {
+//!         for (i, b) in [1, 2, 3, 4].iter().enumerate() { bus.write_byte(0x2000 + i as u16, *b); }
+//!         self.pending = false;
+//!         4 * 3                           // 3 clock cycles per byte
+//!     }
+//! }
+//!
+//! let mut c = CPU::new();
+//! c.attach_dma(Disk { pending: true });
+//! assert_eq!(c.execute(), 4 + 4 * 3 + 1);   // NOP + transfer + HLDA synchronization
+//! assert_eq!(c.bus.read_byte(0x2003), 4);
+//! ```
+use std::{cell::RefCell, rc::Rc};
+use crate::{memory::Bus, step::AccessKind, trace::TraceEvent, CPU};
+
+// Clock cycle needed by the CPU to acknowledge HOLD (HLDA) and to take the bus back
+const HLDA_CYCLES: u32 = 1;
+
+/// A device able to request the bus with HOLD.
+pub trait DmaDevice {
+    /// Returns true while the device asserts HOLD.
+    fn hold(&self) -> bool;
+
+    /// Called when the bus has been granted (HLDA). The device reads and writes memory on the bus, and
+    /// returns the number of clock cycles it held the bus. The bus is released on return.
+    fn transfer(&mut self, bus: &mut Bus) -> u32;
+}
+
+/// Lets the caller keep a handle on a device owned by the CPU.
+impl<T: DmaDevice> DmaDevice for Rc<RefCell<T>> {
+    fn hold(&self) -> bool {
+        self.borrow().hold()
+    }
+
+    fn transfer(&mut self, bus: &mut Bus) -> u32 {
+        self.borrow_mut().transfer(bus)
+    }
+}
+
+impl CPU {
+    /// Attaches a DMA device. When several devices assert HOLD, the first attached is served first.
+    pub fn attach_dma(&mut self, device: impl DmaDevice + 'static) {
+        self.dma.push(Box::new(device));
+    }
+
+    // Grants the bus to the devices asserting HOLD : returns the number of clock cycles the CPU was stalled
+    pub(crate) fn dma_transfers(&mut self) -> u32 {
+        if !self.dma.iter().any(|d| d.hold()) { return 0 }
+        let mut cycles = 0;
+        let journal = self.bus.journaling();
+        if !journal { self.bus.start_journal() }
+        let start = self.bus.journal_len();
+        for d in self.dma.iter_mut() {
+            if d.hold() {
+                cycles += d.transfer(&mut self.bus) + HLDA_CYCLES;
+            }
+        }
+        let writes = if journal { self.bus.journal_since(start) } else { self.bus.take_journal() };
+        for w in writes { self.record(AccessKind::DmaWrite, w.address, w.value) }
+        if cycles > 0 { self.trace(TraceEvent::Hold { clock: self.cycles, cycles }) }
+        cycles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{step::Access, testutil::cpu};
+
+    // Fills memory from $1000, one byte per request, each request taking 3 cycles
+    struct Video {
+        requests: u8,
+        address: u16,
+    }
+
+    impl DmaDevice for Video {
+        fn hold(&self) -> bool {
+            self.requests > 0
+        }
+
+        fn transfer(&mut self, bus: &mut Bus) -> u32 {
+            bus.write_byte(self.address, self.requests);
+            self.address += 1;
+            self.requests -= 1;
+            3
+        }
+    }
+
+    #[test]
+    fn stall() {
+        let video = Rc::new(RefCell::new(Video { requests: 2, address: 0x1000 }));
+        let mut c = cpu(&[(0x0000, &[0x3e, 0x01])]);          // MVI A,$01
+        c.attach_dma(video.clone());
+        assert_eq!(c.execute(), 7 + 3 + 1);
+        assert_eq!(c.reg.a, 1);
+        assert_eq!(c.execute(), 4 + 3 + 1);
+        assert_eq!(c.execute(), 4);
+        assert_eq!(c.cycles(), 23);
+        assert_eq!(c.bus.read_byte(0x1000), 2);
+        assert_eq!(c.bus.read_byte(0x1001), 1);
+        assert!(!video.borrow().hold());
+    }
+
+    #[test]
+    fn trace() {
+        let mut c = cpu(&[]);
+        c.attach_dma(Video { requests: 2, address: 0x1000 });
+        c.set_access_log(true);
+        c.execute();
+        assert_eq!(&c.accesses()[..2], &[
+            Access { kind: AccessKind::DmaWrite, address: 0x1000, value: 2 },
+            Access { kind: AccessKind::Fetch, address: 0x0000, value: 0 },
+        ]);
+    }
+
+    #[test]
+    fn halted() {
+        let mut c = cpu(&[]);
+        c.halt = true;
+        c.attach_dma(Video { requests: 1, address: 0x1000 });
+        assert_eq!(c.execute(), 4 + 3 + 1);
+        assert_eq!(c.bus.read_byte(0x1000), 1);
+    }
+}
@@ -29,6 +29,7 @@ impl Flags {
     }
 
     /// Retrieves condition bits from a byte.
+    #[allow(clippy::wrong_self_convention)]
     pub fn from_byte(&mut self, bflags: u8) {
         self.s = (bflags & 0x80) != 0;
         self.z = (bflags & 0x40) != 0;
@@ -39,6 +40,7 @@ impl Flags {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
     #[test]
@@ -0,0 +1,445 @@
+//! Intel 8259A programmable interrupt controller, in 8080 mode.
+//!
+//! The controller is attached to two I/O ports (A0 = bit 0 of the port number) and acts as the CPU
+//! interrupt controller: when an interrupt is acknowledged, it supplies a CALL to the vector of the
+//! highest priority request.
+//! ```rust
+//! use std::{cell::RefCell, rc::Rc};
+//! use intel8080::{CPU, i8259::I8259, memory::IoDevice};
+//!
+//! let pic = Rc::new(RefCell::new(I8259::new()));
+//! let mut c = CPU::new();
+//! c.bus.attach_io(0x20..=0x21, pic.clone());
+//! c.int_controller = Some(Box::new(pic.clone()));
+//!
+//! // ICW1 : single, interval 4, vectors at $1000 + 4 * IR
+//! pic.borrow_mut().output(0x20, 0x16);
+//! // ICW2 : vector high byte
+//! pic.borrow_mut().output(0x21, 0x10);
+//!
+//! c.sp = 0xff00;
+//! c.inte = true;
+//! pic.borrow_mut().set_ir(3, true);
+//! c.execute();
+//! assert_eq!(c.pc, 0x100c);
+//! ```
+use std::{cell::RefCell, rc::Rc};
+use crate::{interrupt::InterruptController, memory::IoDevice};
+
+// Initialization sequence state : next expected ICW
+#[derive(Clone, Copy, PartialEq)]
+enum Init {
+    Ready,
+    Icw2,
+    Icw3,
+    Icw4,
+}
+
+pub struct I8259 {
+    icw1: u8,
+    icw2: u8,
+    icw3: u8,
+    icw4: u8,
+    init: Init,
+    /// Interrupt request register
+    pub irr: u8,
+    /// In-service register
+    pub isr: u8,
+    /// Interrupt mask register
+    pub imr: u8,
+    // IR inputs levels, for edge detection
+    ir: u8,
+    // Lowest priority level (7 after initialization)
+    lowest: u8,
+    // OCW3 : status read (ISR instead of IRR), poll command, special mask mode
+    read_isr: bool,
+    poll: bool,
+    special_mask: bool,
+    rotate_aeoi: bool,
+    // Level being acknowledged, and whether its vector comes from a slave
+    inta_level: Option<u8>,
+    inta_slave: bool,
+    slaves: [Option<Rc<RefCell<I8259>>>; 8],
+}
+
+impl Default for I8259 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl I8259 {
+    /// Creates an unprogrammed controller. It has to be initialized by the program (ICW1 to ICW4).
+    pub fn new() -> I8259 {
+        I8259 {
+            icw1: 0,
+            icw2: 0,
+            icw3: 0,
+            icw4: 0,
+            init: Init::Ready,
+            irr: 0,
+            isr: 0,
+            imr: 0,
+            ir: 0,
+            lowest: 7,
+            read_isr: false,
+            poll: false,
+            special_mask: false,
+            rotate_aeoi: false,
+            inta_level: None,
+            inta_slave: false,
+            slaves: Default::default(),
+        }
+    }
+
+    /// Connects the INT output of a slave controller to the IR input of this master.
+    pub fn cascade(&mut self, ir: u8, slave: Rc<RefCell<I8259>>) {
+        self.slaves[usize::from(ir & 0x07)] = Some(slave);
+    }
+
+    /// Sets the level of an IR input. In edge triggered mode a request is latched on the rising edge,
+    /// in level triggered mode the request follows the input.
+    pub fn set_ir(&mut self, line: u8, level: bool) {
+        let bit = 1 << (line & 0x07);
+        let rising = level && self.ir & bit == 0;
+        if level { self.ir |= bit } else { self.ir &= !bit }
+        if self.level_triggered() {
+            if level { self.irr |= bit } else { self.irr &= !bit }
+        } else if rising {
+            self.irr |= bit;
+        }
+    }
+
+    fn level_triggered(&self) -> bool {
+        self.icw1 & 0x08 != 0
+    }
+
+    fn single(&self) -> bool {
+        self.icw1 & 0x02 != 0
+    }
+
+    fn auto_eoi(&self) -> bool {
+        self.icw4 & 0x02 != 0
+    }
+
+    // Is a slave connected on this level ?
+    fn cascaded(&self, level: u8) -> bool {
+        !self.single() && self.icw3 & (1 << level) != 0 && self.slaves[usize::from(level)].is_some()
+    }
+
+    // Requests, including the INT outputs of the slaves
+    fn requests(&self) -> u8 {
+        let mut irr = self.irr;
+        for level in 0..8 {
+            if self.cascaded(level) && self.slaves[usize::from(level)].as_ref().is_some_and(|s| s.borrow().int()) {
+                irr |= 1 << level;
+            }
+        }
+        irr
+    }
+
+    // Priority of a level : 0 is the highest
+    fn priority(&self, level: u8) -> u8 {
+        level.wrapping_sub(self.lowest).wrapping_sub(1) & 0x07
+    }
+
+    // Highest priority level set in a register
+    fn highest(&self, reg: u8) -> Option<u8> {
+        (0..8).filter(|l| reg & (1 << l) != 0).min_by_key(|&l| self.priority(l))
+    }
+
+    // Highest priority request allowed to interrupt the in-service levels (fully nested mode)
+    fn resolve(&self) -> Option<u8> {
+        let req = self.highest(self.requests() & !self.imr)?;
+        // In special mask mode, masked levels do not inhibit lower priority requests
+        let isr = if self.special_mask { self.isr & !self.imr } else { self.isr };
+        match self.highest(isr) {
+            None => Some(req),
+            Some(s) => {
+                let p = self.priority(req);
+                // Special fully nested mode : a slave can interrupt while another of its requests is in service
+                let sfnm = self.icw4 & 0x10 != 0 && self.cascaded(req);
+                if p < self.priority(s) || (sfnm && p == self.priority(s)) { Some(req) } else { None }
+            }
+        }
+    }
+
+    // Sets the in-service bit of the acknowledged level and clears its request
+    fn acknowledge(&mut self, level: u8) {
+        self.isr |= 1 << level;
+        self.irr &= !(1 << level);
+    }
+
+    // Clears an in-service bit, rotating the priorities if requested
+    fn eoi(&mut self, level: u8, rotate: bool) {
+        let bit = 1 << level;
+        self.isr &= !bit;
+        if rotate { self.lowest = level }
+        // A level triggered input still high raises a new request
+        if self.level_triggered() { self.irr |= self.ir & bit }
+    }
+
+    // CALL address low byte : vector interval of 4 or 8 bytes
+    fn vector_low(&self, level: u8) -> u8 {
+        match self.icw1 & 0x04 != 0 {
+            true => (self.icw1 & 0xe0) | (level << 2),
+            false => (self.icw1 & 0xc0) | (level << 3),
+        }
+    }
+
+    fn icw1(&mut self, data: u8) {
+        self.icw1 = data;
+        self.icw4 = 0;
+        self.imr = 0;
+        self.isr = 0;
+        self.irr &= if self.level_triggered() { self.ir } else { 0 };
+        self.lowest = 7;
+        self.read_isr = false;
+        self.poll = false;
+        self.special_mask = false;
+        self.rotate_aeoi = false;
+        self.init = Init::Icw2;
+    }
+
+    fn ocw2(&mut self, data: u8) {
+        let level = data & 0x07;
+        match data >> 5 {
+            // Non-specific EOI, with or without rotation
+            0b001 | 0b101 => if let Some(l) = self.highest(self.isr) { self.eoi(l, data & 0x80 != 0) },
+            // Specific EOI, with or without rotation
+            0b011 | 0b111 => self.eoi(level, data & 0x80 != 0),
+            // Rotate in automatic EOI mode : set / clear
+            0b100 => self.rotate_aeoi = true,
+            0b000 => self.rotate_aeoi = false,
+            // Set priority
+            0b110 => self.lowest = level,
+            _ => {}
+        }
+    }
+
+    fn ocw3(&mut self, data: u8) {
+        if data & 0x40 != 0 { self.special_mask = data & 0x20 != 0 }
+        if data & 0x02 != 0 { self.read_isr = data & 0x01 != 0 }
+        self.poll = data & 0x04 != 0;
+    }
+
+    // Poll command : the read acts as an interrupt acknowledge
+    fn poll_word(&mut self) -> u8 {
+        self.poll = false;
+        match self.resolve() {
+            Some(level) => {
+                self.acknowledge(level);
+                0x80 | level
+            },
+            None => 0,
+        }
+    }
+}
+
+impl IoDevice for I8259 {
+    fn input(&mut self, port: u8) -> u8 {
+        match port & 0x01 {
+            0 if self.poll => self.poll_word(),
+            0 if self.read_isr => self.isr,
+            0 => self.irr,
+            _ => self.imr,
+        }
+    }
+
+    fn output(&mut self, port: u8, data: u8) {
+        match (port & 0x01, self.init) {
+            (0, _) if data & 0x10 != 0 => self.icw1(data),
+            (0, _) if data & 0x08 != 0 => self.ocw3(data),
+            (0, _) => self.ocw2(data),
+            (_, Init::Icw2) => {
+                self.icw2 = data;
+                self.init = match (self.single(), self.icw1 & 0x01 != 0) {
+                    (false, _) => Init::Icw3,
+                    (true, true) => Init::Icw4,
+                    (true, false) => Init::Ready,
+                };
+            },
+            (_, Init::Icw3) => {
+                self.icw3 = data;
+                self.init = if self.icw1 & 0x01 != 0 { Init::Icw4 } else { Init::Ready };
+            },
+            (_, Init::Icw4) => {
+                self.icw4 = data;
+                self.init = Init::Ready;
+            },
+            (_, Init::Ready) => self.imr = data,
+        }
+    }
+}
+
+impl InterruptController for I8259 {
+    fn int(&self) -> bool {
+        self.init == Init::Ready && self.resolve().is_some()
+    }
+
+    fn inta(&mut self, n: u8) -> u8 {
+        match n {
+            0 => {
+                // Without any request left (spurious interrupt), the controller responds with IR7
+                self.inta_level = self.resolve();
+                self.inta_slave = false;
+                if let Some(level) = self.inta_level {
+                    if self.cascaded(level) {
+                        self.inta_slave = true;
+                        if let Some(s) = &self.slaves[usize::from(level)] { s.borrow_mut().inta(0); }
+                    }
+                    self.acknowledge(level);
+                }
+                0xcd
+            },
+            1 | 2 => {
+                let level = self.inta_level.unwrap_or(7);
+                let data = match (self.inta_slave, &self.slaves[usize::from(level)]) {
+                    (true, Some(s)) => s.borrow_mut().inta(n),
+                    _ => if n == 1 { self.vector_low(level) } else { self.icw2 },
+                };
+                if n == 2 && self.auto_eoi() {
+                    if let Some(level) = self.inta_level { self.eoi(level, self.rotate_aeoi) }
+                }
+                data
+            },
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ICW1 + ICW2 : single, interval 4, vectors at $2000, ICW4 with the given value
+    fn pic(icw4: u8) -> I8259 {
+        let mut p = I8259::new();
+        p.output(0, 0x17);
+        p.output(1, 0x20);
+        p.output(1, icw4);
+        p
+    }
+
+    fn inta(p: &mut I8259) -> u16 {
+        assert_eq!(p.inta(0), 0xcd);
+        u16::from(p.inta(1)) | (u16::from(p.inta(2)) << 8)
+    }
+
+    #[test]
+    fn vector_interval_8() {
+        let mut p = I8259::new();
+        p.output(0, 0x52);
+        p.output(1, 0x30);
+        p.set_ir(5, true);
+        assert!(p.int());
+        assert_eq!(inta(&mut p), 0x3068);
+    }
+
+    #[test]
+    fn fully_nested() {
+        let mut p = pic(0);
+        p.set_ir(4, true);
+        assert_eq!(inta(&mut p), 0x2010);
+        // lower priority : blocked while IR4 is in service
+        p.set_ir(6, true);
+        assert!(!p.int());
+        // higher priority : nested
+        p.set_ir(1, true);
+        assert_eq!(inta(&mut p), 0x2004);
+        assert_eq!(p.isr, 0x12);
+        // non-specific EOI clears IR1, then IR4
+        p.output(0, 0x20);
+        assert!(!p.int());
+        p.output(0, 0x20);
+        assert!(p.int());
+        assert_eq!(inta(&mut p), 0x2018);
+    }
+
+    #[test]
+    fn mask() {
+        let mut p = pic(0);
+        p.output(1, 0x08);
+        p.set_ir(3, true);
+        assert!(!p.int());
+        p.output(1, 0x00);
+        assert!(p.int());
+    }
+
+    #[test]
+    fn edge_level() {
+        let mut p = pic(0);
+        p.set_ir(2, true);
+        inta(&mut p);
+        p.output(0, 0x62);                  // specific EOI IR2
+        // edge triggered : no new request while the input stays high
+        assert!(!p.int());
+
+        let mut p = I8259::new();
+        p.output(0, 0x1b);                  // level triggered, single, ICW4
+        p.output(1, 0x20);
+        p.output(1, 0x00);
+        p.set_ir(2, true);
+        inta(&mut p);
+        p.output(0, 0x62);
+        assert!(p.int());
+        p.set_ir(2, false);
+        assert!(!p.int());
+    }
+
+    #[test]
+    fn rotating_priority() {
+        let mut p = pic(0);
+        p.set_ir(0, true);
+        p.set_ir(3, true);
+        assert_eq!(inta(&mut p), 0x2000);
+        p.output(0, 0xa0);                  // rotate on non-specific EOI : IR0 becomes the lowest
+        p.set_ir(0, false);
+        p.set_ir(0, true);
+        assert_eq!(inta(&mut p), 0x200c);
+    }
+
+    #[test]
+    fn auto_eoi() {
+        let mut p = pic(0x02);
+        p.set_ir(6, true);
+        inta(&mut p);
+        assert_eq!(p.isr, 0);
+    }
+
+    #[test]
+    fn read_registers() {
+        let mut p = pic(0);
+        p.output(1, 0x01);
+        p.set_ir(7, true);
+        assert_eq!(p.input(0), 0x80);
+        assert_eq!(p.input(1), 0x01);
+        p.output(0, 0x0b);                  // OCW3 : read ISR
+        assert_eq!(p.input(0), 0x00);
+        p.output(0, 0x0c);                  // OCW3 : poll
+        assert_eq!(p.input(0), 0x87);
+        assert_eq!(p.isr, 0x80);
+    }
+
+    #[test]
+    fn cascade() {
+        let slave = Rc::new(RefCell::new(I8259::new()));
+        let mut master = I8259::new();
+        master.cascade(2, slave.clone());
+        // master : cascade, interval 4, vectors at $2000, IR2 has a slave
+        master.output(0, 0x15);
+        master.output(1, 0x20);
+        master.output(1, 0x04);
+        master.output(1, 0x00);
+        // slave : vectors at $3000, ID 2
+        slave.borrow_mut().output(0, 0x15);
+        slave.borrow_mut().output(1, 0x30);
+        slave.borrow_mut().output(1, 0x02);
+        slave.borrow_mut().output(1, 0x00);
+        slave.borrow_mut().set_ir(1, true);
+        assert!(master.int());
+        assert_eq!(inta(&mut master), 0x3004);
+        assert_eq!(master.isr, 0x04);
+        assert_eq!(slave.borrow().isr, 0x02);
+    }
+}
@@ -0,0 +1,250 @@
+use crate::CPU;
+
+// Maximum length of a detected loop, in bytes
+const MAX_LOOP: u16 = 32;
+// Maximum number of cycles skipped by a single execute() call
+const MAX_SKIP: u64 = 1 << 30;
+
+// Idle loop : a short loop that always comes back to its first instruction in the same state, without
+// writing to memory or to an output port, and reading the same values from the same input ports during
+// two iterations in a row. Only an interrupt or a device event can make it exit.
+pub(crate) struct IdleDetector {
+    // First instruction of the loop, CPU state and virtual clock when it was reached
+    head: Option<(u16, [u8; 11], u64)>,
+    // Memory or output port written since the loop head has been reached
+    dirty: bool,
+    // Ports and values read since the loop head has been reached, and during the previous iteration
+    pub(crate) inputs: Vec<(u8, u8)>,
+    previous: Option<Vec<(u8, u8)>>,
+    // Idle loops are not fast-forwarded beyond this cycle count (end of the caller's budget)
+    pub(crate) limit: u64,
+}
+
+impl IdleDetector {
+    pub(crate) fn new() -> IdleDetector {
+        IdleDetector { head: None, dirty: false, inputs: Vec::new(), previous: None, limit: u64::MAX }
+    }
+}
+
+// Instructions writing to memory or to an output port
+fn writes(opcode: u8) -> bool {
+    matches!(opcode, 0x02 | 0x12 | 0x22 | 0x32 | 0x34 | 0x35 | 0x36 | 0x70..=0x75 | 0x77 |
+        0xC5 | 0xD5 | 0xE5 | 0xF5 | 0xE3 | 0xD3 | 0xCD | 0xDC | 0xD4 | 0xCC | 0xC4 | 0xFC | 0xF4 | 0xEC | 0xE4) ||
+        opcode & 0xC7 == 0xC7
+}
+
+// JMP and conditional jumps
+fn is_jump(opcode: u8) -> bool {
+    opcode == 0xC3 || opcode & 0xC7 == 0xC2
+}
+
+impl CPU {
+    /// Enables or disables idle loop detection. A busy wait (`JMP $`, or a loop polling an input port
+    /// that keeps returning the same value) is fast-forwarded to the next scheduled event, or to the
+    /// end of the cycle budget of run_cycles() and of the current execute_timed() slice.
+    /// The skipped cycles are added to the virtual clock and returned by execute().
+    /// Nothing is fast-forwarded while DMA devices are attached, as they may change memory at any time.
+    /// ```rust
+    /// use intel8080::CPU;
+    /// let mut c = CPU::new();
+    /// c.set_idle_detection(true);
+    /// c.bus.write_byte(0x0000, 0xc3);     // JMP $0000
+    /// let r = c.run_cycles(1_000_000);
+    /// assert_eq!(r.cycles, 1_000_000);
+    /// ```
+    pub fn set_idle_detection(&mut self, enabled: bool) {
+        self.idle = if enabled { Some(IdleDetector::new()) } else { None };
+    }
+
+    // Sets the cycle count idle loops can be fast-forwarded to, returns the previous one
+    pub(crate) fn set_idle_limit(&mut self, limit: u64) -> u64 {
+        match self.idle.as_mut() {
+            Some(d) => std::mem::replace(&mut d.limit, limit),
+            None => u64::MAX,
+        }
+    }
+
+    // Cycle count the idle CPU can be fast-forwarded to. DMA devices may change memory at any time : no fast-forward.
+    fn idle_target(&self) -> Option<u64> {
+        if !self.dma.is_empty() { return None }
+        let limit = self.idle.as_ref()?.limit;
+        let target = self.scheduler.next_event().map_or(limit, |e| e.min(limit));
+        if target == u64::MAX || target <= self.cycles() { None } else { Some(target) }
+    }
+
+    // Halted CPU waiting for an interrupt : returns the fast-forwarded cycles
+    pub(crate) fn idle_halt(&mut self) -> u64 {
+        match self.idle_target() {
+            Some(target) => (target - self.cycles()).min(MAX_SKIP),
+            None => 0,
+        }
+    }
+
+    // Called after each instruction : returns the fast-forwarded cycles if an idle loop is detected
+    pub(crate) fn idle_loop(&mut self, pc: u16, opcode: u8) -> u64 {
+        let state = [self.reg.a, self.reg.b, self.reg.c, self.reg.d, self.reg.e, self.reg.h, self.reg.l,
+            self.flags.as_byte(), self.sp as u8, (self.sp >> 8) as u8, u8::from(self.inte)];
+        let (now, target) = (self.cycles(), self.idle_target());
+        let d = match self.idle.as_mut() { Some(d) => d, None => return 0 };
+        if writes(opcode) { d.dirty = true }
+        if !is_jump(opcode) {
+            // A short loop reads at most MAX_LOOP / 2 ports per iteration : this code is not one
+            if d.inputs.len() > usize::from(MAX_LOOP / 2) {
+                d.inputs.clear();
+                d.head = None;
+            }
+            return 0
+        }
+        // The port reads are kept for one iteration, from a jump to the next one
+        let inputs = std::mem::take(&mut d.inputs);
+        // Taken backward jump : the loop head is the jump target
+        if self.pc > pc || pc - self.pc > MAX_LOOP { return 0 }
+        match d.head {
+            Some((head, s, cycles)) if head == self.pc && s == state && !d.dirty => {
+                // The polled ports must return the same values during two iterations
+                if d.previous.as_ref() != Some(&inputs) {
+                    d.head = Some((head, s, now));
+                    d.previous = Some(inputs);
+                    return 0
+                }
+                let target = match target { Some(t) => t, None => { d.head = Some((head, s, now)); return 0 } };
+                // Whole iterations of the loop are skipped
+                let period = now - cycles;
+                let skip = (target - now).div_ceil(period).saturating_mul(period).min(MAX_SKIP / period * period);
+                d.head = Some((head, s, now + skip));
+                skip
+            },
+            _ => {
+                d.head = Some((self.pc, state, now));
+                d.dirty = false;
+                d.previous = None;
+                0
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{memory::IoDevice, testutil::cpu};
+    use std::{cell::Cell, rc::Rc};
+
+    struct Status(Rc<Cell<u8>>);
+
+    impl IoDevice for Status {
+        fn input(&mut self, _port: u8) -> u8 { self.0.get() }
+        fn output(&mut self, _port: u8, _data: u8) {}
+    }
+
+    #[test]
+    fn jmp_self() {
+        let mut c = cpu(&[(0x0000, &[0xc3, 0x00, 0x00])]);    // JMP $0000
+        c.set_idle_detection(true);
+        c.schedule(1000, |c| { c.reg.b = 1; None });
+        let r = c.run_cycles(100);
+        assert_eq!(r.cycles, 100);
+        c.run_until(|c| c.reg.b == 1);
+        // fast-forwarded to the event, fired before the next iteration
+        assert_eq!(c.cycles(), 1010);
+        assert_eq!(c.run_cycles(5000).cycles, 5000);
+    }
+
+    #[test]
+    fn polling_loop() {
+        // loop : IN $01 / ANI $01 / JZ loop / HLT
+        let mut c = cpu(&[(0x0000, &[0xdb, 0x01, 0xe6, 0x01, 0xca, 0x00, 0x00, 0x76])]);
+        c.set_idle_detection(true);
+        // the status port is ready after 10000 cycles
+        let status = Rc::new(Cell::new(0));
+        c.bus.attach_io(0x01..=0x01, Status(status.clone()));
+        c.schedule(10000, move |_| { status.set(1); None });
+        let r = c.run_until(|c| c.halt);
+        // 1 iteration = 27 cycles : fast-forwarded to the first iteration after 10000 cycles
+        assert_eq!(r.cycles, 371 * 27 + 27 + 7);
+        assert_eq!(c.pc, 0x0008);
+    }
+
+    struct Countdown(u8);
+
+    impl IoDevice for Countdown {
+        fn input(&mut self, _port: u8) -> u8 {
+            self.0 -= 1;
+            if self.0 == 0 { 0x80 } else { self.0 }
+        }
+        fn output(&mut self, _port: u8, _data: u8) {}
+    }
+
+    #[test]
+    fn changing_input() {
+        // loop : IN $01 / ANI $80 / JZ loop / HLT
+        let mut c = cpu(&[(0x0000, &[0xdb, 0x01, 0xe6, 0x80, 0xca, 0x00, 0x00, 0x76])]);
+        c.set_idle_detection(true);
+        // same state at each iteration, but the port returns 19, 18... 1, then ready
+        c.bus.attach_io(0x01..=0x01, Countdown(20));
+        c.schedule(100_000, |_| None);
+        let r = c.run_until(|c| c.halt);
+        assert_eq!(r.cycles, 20 * 27 + 7);
+    }
+
+    #[test]
+    fn long_polling_loops() {
+        // loop : IN $01 / 40 x NOP / JMP loop
+        let mut nops = [0x00; 45];
+        nops[..2].copy_from_slice(&[0xdb, 0x01]);
+        nops[42..].copy_from_slice(&[0xc3, 0x00, 0x00]);
+        let mut c = cpu(&[(0x0000, &nops)]);
+        c.set_idle_detection(true);
+        c.bus.attach_io(0x01..=0x01, Status(Rc::new(Cell::new(0))));
+        c.run_cycles(100_000);
+        assert!(c.idle.as_ref().unwrap().inputs.len() <= 1);
+        // loop without jumps : IN $01 / RST 0
+        let mut c = cpu(&[(0x0000, &[0xdb, 0x01, 0xc7])]);
+        c.set_idle_detection(true);
+        c.bus.attach_io(0x01..=0x01, Status(Rc::new(Cell::new(0))));
+        c.run_cycles(100_000);
+        assert!(c.idle.as_ref().unwrap().inputs.len() <= usize::from(MAX_LOOP / 2) + 1);
+    }
+
+    #[test]
+    fn dma() {
+        struct Idle(Rc<Cell<u32>>);
+        impl crate::dma::DmaDevice for Idle {
+            fn hold(&self) -> bool { self.0.set(self.0.get() + 1); false }
+            fn transfer(&mut self, _bus: &mut crate::memory::Bus) -> u32 { 0 }
+        }
+        let mut c = cpu(&[(0x0000, &[0xc3, 0x00, 0x00])]);    // JMP $0000
+        c.set_idle_detection(true);
+        let checks = Rc::new(Cell::new(0));
+        c.attach_dma(Idle(checks.clone()));
+        c.run_cycles(1000);
+        // an instruction every 10 cycles : not fast-forwarded
+        assert_eq!(checks.get(), 100);
+    }
+
+    #[test]
+    fn not_idle() {
+        // INR A / JMP $0000 : state changes at each iteration
+        let mut c = cpu(&[(0x0000, &[0x3c, 0xc3, 0x00, 0x00])]);
+        c.set_idle_detection(true);
+        c.run_cycles(150);
+        assert_eq!(c.reg.a, 10);
+        // STA $1000 / JMP $0000 : writes to memory
+        let mut c = cpu(&[(0x0000, &[0x32, 0x00, 0x10, 0xc3, 0x00, 0x00])]);
+        c.set_idle_detection(true);
+        c.schedule(10000, |_| None);
+        c.run_cycles(230);
+        assert_eq!(c.cycles(), 230);
+    }
+
+    #[test]
+    fn halted() {
+        let mut c = cpu(&[(0x0000, &[0x76])]);                 // HLT
+        c.set_idle_detection(true);
+        c.inte = true;
+        c.schedule(5000, |c| { c.int = (true, 0xcf); None });
+        c.run_until(|c| !c.halt);
+        assert_eq!(c.cycles(), 5000 + 11);
+        assert_eq!(c.pc, 0x0008);
+    }
+}
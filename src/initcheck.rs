@@ -0,0 +1,106 @@
+//! Uninitialized memory reads.
+//!
+//! When the bus tracks the written bytes (Bus::track_writes()), reading or executing a byte which has never
+//! been written sends an Uninitialized trace event. InitChecker is a trace sink which reports the first
+//! uninitialized access to each address, with the instruction which made it. Such a program only works
+//! with the power-on contents of the memory.
+//! ```rust
+//! use intel8080::{CPU, initcheck::InitChecker, power::Fill};
+//! use std::{cell::RefCell, rc::Rc};
+//! let mut c = CPU::new();
+//! c.bus.track_writes(true);
+//! c.power_on(Fill::Pattern(0x00));    // does not initialize memory
+//! c.bus.write_byte(0x0000, 0x3a);     // LDA $2000
+//! c.bus.write_word(0x0001, 0x2000);
+//! c.bus.write_byte(0x0003, 0x76);     // HLT
+//! let i = Rc::new(RefCell::new(InitChecker::new()));
+//! c.set_tracer(i.clone());
+//! c.run_until(|c| c.halt);
+//! assert_eq!(i.borrow().reads()[0].to_string(), "$0000 : read of uninitialized memory at $2000");
+//! ```
+use std::{collections::HashSet, fmt};
+use crate::{step::{Access, AccessKind}, trace::{TraceEvent, TraceSink}};
+
+/// An access to uninitialized memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UninitializedRead {
+    /// Address of the instruction
+    pub address: u16,
+    pub access: Access,
+}
+
+impl fmt::Display for UninitializedRead {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let what = if self.access.kind == AccessKind::Fetch { "execution" } else { "read" };
+        write!(f, "${:04x} : {} of uninitialized memory at ${:04x}", self.address, what, self.access.address)
+    }
+}
+
+#[derive(Default)]
+pub struct InitChecker {
+    reads: Vec<UninitializedRead>,
+    reported: HashSet<u16>,
+    // Accesses of the current instruction, which come before its Instruction event
+    pending: Vec<Access>,
+}
+
+impl InitChecker {
+    pub fn new() -> InitChecker {
+        InitChecker::default()
+    }
+
+    pub fn reads(&self) -> &[UninitializedRead] {
+        &self.reads
+    }
+
+    /// Forgets the reads : they will be reported again.
+    pub fn clear(&mut self) {
+        self.reads.clear();
+        self.reported.clear();
+    }
+}
+
+impl TraceSink for InitChecker {
+    fn event(&mut self, event: &TraceEvent) {
+        match *event {
+            TraceEvent::Uninitialized(access) => self.pending.push(access),
+            TraceEvent::Instruction { address, .. } => {
+                for access in std::mem::take(&mut self.pending) {
+                    if self.reported.insert(access.address) { self.reads.push(UninitializedRead { address, access }) }
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{attach, cpu};
+
+    #[test]
+    fn reads() {
+        // $0000 : LXI SP,$ff00 / POP B / LHLD $2000 / SHLD $2000 / LHLD $2000 / JMP $0100
+        let mut c = cpu(&[(0x0000, &[0x31, 0x00, 0xff, 0xc1, 0x2a, 0x00, 0x20, 0x22, 0x00, 0x20, 0x2a, 0x00, 0x20, 0xc3, 0x00, 0x01])]);
+        c.bus.track_writes(true);
+        c.bus.set_initialized(0x0000..=0x000f);
+        let i = attach(&mut c, InitChecker::new());
+        for _ in 0..7 { c.execute(); }
+        let reads: Vec<(u16, AccessKind, u16)> = i.borrow().reads().iter().map(|r| (r.address, r.access.kind, r.access.address)).collect();
+        assert_eq!(reads, vec![
+            (0x0003, AccessKind::StackRead, 0xff00), (0x0003, AccessKind::StackRead, 0xff01),
+            (0x0004, AccessKind::Read, 0x2000), (0x0004, AccessKind::Read, 0x2001),
+            (0x0100, AccessKind::Fetch, 0x0100),
+        ]);
+        assert_eq!(i.borrow().reads()[4].to_string(), "$0100 : execution of uninitialized memory at $0100");
+    }
+
+    #[test]
+    fn disabled() {
+        let mut c = cpu(&[]);
+        let i = attach(&mut c, InitChecker::new());
+        c.execute();
+        assert!(i.borrow().reads().is_empty());
+    }
+}
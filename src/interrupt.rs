@@ -0,0 +1,208 @@
+use std::{cell::RefCell, rc::Rc};
+
+/// A device driving the CPU INT line, like an 8228 system controller or an 8259A.
+/// When the interrupt is accepted, the CPU runs interrupt acknowledge (INTA) cycles and executes the
+/// instruction the device puts on the data bus: usually a RST, or a CALL when the device supplies 3 bytes.
+pub trait InterruptController {
+    /// Returns true while an interrupt request is pending.
+    fn int(&self) -> bool;
+
+    /// Returns the byte placed on the data bus during INTA cycle n : 0 for the opcode,
+    /// 1 and 2 for the operands of a multi-byte instruction. The request is considered acknowledged
+    /// once the CPU has read the last byte of the instruction.
+    fn inta(&mut self, n: u8) -> u8;
+}
+
+/// Lets the caller keep a handle on a device owned by the CPU.
+/// ```rust
+/// use std::{cell::RefCell, rc::Rc};
+/// use intel8080::{CPU, interrupt::InterruptController};
+///
+/// struct Call(Option<u16>);
+///
+/// impl InterruptController for Call {
+///     fn int(&self) -> bool { self.0.is_some() }
+///     fn inta(&mut self, n: u8) -> u8 {
+///         let addr = self.0.unwrap_or(0);
+///         match n {
+///             0 => 0xcd,                      // CALL
+///             1 => addr as u8,
+///             _ => { self.0 = None; (addr >> 8) as u8 }
+///         }
+///     }
+/// }
+///
+/// let device = Rc::new(RefCell::new(Call(None)));
+/// let mut c = CPU::new();
+/// c.int_controller = Some(Box::new(device.clone()));
+/// c.sp = 0xff00;
+/// c.inte = true;
+/// device.borrow_mut().0 = Some(0x1234);   // the device requests an interrupt
+/// c.execute();
+/// assert_eq!(c.pc, 0x1234);
+/// ```
+impl<T: InterruptController> InterruptController for Rc<RefCell<T>> {
+    fn int(&self) -> bool {
+        self.borrow().int()
+    }
+
+    fn inta(&mut self, n: u8) -> u8 {
+        self.borrow_mut().inta(n)
+    }
+}
+
+/// Chooses which of the pending interrupt requests is acknowledged first.
+pub trait PriorityResolver {
+    /// `pending` holds the asserted lines in ascending order and is never empty. Returns the line to acknowledge.
+    fn resolve(&mut self, pending: &[usize]) -> usize;
+}
+
+/// Fixed priority : line 0 has the highest priority.
+pub struct FixedPriority;
+
+impl PriorityResolver for FixedPriority {
+    fn resolve(&mut self, pending: &[usize]) -> usize {
+        pending[0]
+    }
+}
+
+/// Rotating priority : the line just acknowledged gets the lowest priority.
+#[derive(Default)]
+pub struct RotatingPriority {
+    last: Option<usize>,
+}
+
+impl PriorityResolver for RotatingPriority {
+    fn resolve(&mut self, pending: &[usize]) -> usize {
+        let line = match self.last {
+            Some(last) => *pending.iter().find(|&&l| l > last).unwrap_or(&pending[0]),
+            None => pending[0],
+        };
+        self.last = Some(line);
+        line
+    }
+}
+
+/// Interrupt lines shared by several devices. Each device asserts its own line with the instruction
+/// (usually a RST) to execute when its request is acknowledged. A request stays pending until
+/// it is acknowledged by the CPU or withdrawn by the device.
+/// ```rust
+/// use std::{cell::RefCell, rc::Rc};
+/// use intel8080::{CPU, interrupt::InterruptLines};
+///
+/// let lines = Rc::new(RefCell::new(InterruptLines::new(8)));
+/// let mut c = CPU::new();
+/// c.int_controller = Some(Box::new(lines.clone()));
+/// c.sp = 0xff00;
+/// c.inte = true;
+/// lines.borrow_mut().assert_rst(5, 5);    // device on line 5 requests RST 5
+/// lines.borrow_mut().assert_rst(2, 2);    // device on line 2 requests RST 2
+/// c.execute();
+/// assert_eq!(c.pc, 0x0010);               // line 2 has the highest priority
+/// assert!(lines.borrow().is_asserted(5));
+/// ```
+pub struct InterruptLines {
+    requests: Vec<Option<Vec<u8>>>,
+    resolver: Box<dyn PriorityResolver>,
+    // Instruction being acknowledged
+    inta: Vec<u8>,
+}
+
+impl InterruptLines {
+    /// Creates a set of interrupt lines with a fixed priority.
+    pub fn new(lines: usize) -> InterruptLines {
+        InterruptLines {
+            requests: vec![None; lines],
+            resolver: Box::new(FixedPriority),
+            inta: Vec::new(),
+        }
+    }
+
+    /// Replaces the priority resolver.
+    pub fn set_resolver(&mut self, resolver: impl PriorityResolver + 'static) {
+        self.resolver = Box::new(resolver);
+    }
+
+    /// Asserts a line. `instruction` holds the bytes supplied to the CPU when the request is acknowledged.
+    /// A request already pending on this line is replaced.
+    pub fn assert(&mut self, line: usize, instruction: &[u8]) {
+        self.requests[line] = Some(instruction.to_vec());
+    }
+
+    /// Asserts a line with a RST n instruction.
+    pub fn assert_rst(&mut self, line: usize, n: u8) {
+        self.assert(line, &[0xc7 | ((n & 0x07) << 3)]);
+    }
+
+    /// Withdraws the request pending on a line.
+    pub fn deassert(&mut self, line: usize) {
+        self.requests[line] = None;
+    }
+
+    /// Is a request pending on this line ?
+    pub fn is_asserted(&self, line: usize) -> bool {
+        self.requests[line].is_some()
+    }
+}
+
+impl InterruptController for InterruptLines {
+    fn int(&self) -> bool {
+        self.requests.iter().any(|r| r.is_some())
+    }
+
+    fn inta(&mut self, n: u8) -> u8 {
+        if n == 0 {
+            let pending: Vec<usize> = (0..self.requests.len()).filter(|&l| self.requests[l].is_some()).collect();
+            if pending.is_empty() { return 0 }
+            let line = self.resolver.resolve(&pending);
+            self.inta = self.requests[line].take().unwrap_or_default();
+        }
+        self.inta.get(usize::from(n)).copied().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn lines_fixed_priority() {
+        let mut l = InterruptLines::new(4);
+        l.assert_rst(3, 7);
+        l.assert_rst(1, 1);
+        assert!(l.int());
+        assert_eq!(l.inta(0), 0xcf);
+        assert!(!l.is_asserted(1));
+        assert_eq!(l.inta(0), 0xff);
+        assert!(!l.int());
+    }
+
+    #[test]
+    fn lines_deassert() {
+        let mut l = InterruptLines::new(4);
+        l.assert_rst(0, 1);
+        l.deassert(0);
+        assert!(!l.int());
+    }
+
+    #[test]
+    fn lines_call() {
+        let mut l = InterruptLines::new(2);
+        l.assert(1, &[0xcd, 0x34, 0x12]);
+        assert_eq!(l.inta(0), 0xcd);
+        assert_eq!(l.inta(1), 0x34);
+        assert_eq!(l.inta(2), 0x12);
+    }
+
+    #[test]
+    fn lines_rotating_priority() {
+        let mut l = InterruptLines::new(4);
+        l.set_resolver(RotatingPriority::default());
+        for line in 0..4 { l.assert_rst(line, line as u8) }
+        assert_eq!(l.inta(0), 0xc7);
+        l.assert_rst(0, 0);
+        assert_eq!(l.inta(0), 0xcf);
+        assert_eq!(l.inta(0), 0xd7);
+        assert_eq!(l.inta(0), 0xdf);
+        assert_eq!(l.inta(0), 0xc7);
+    }
+}
@@ -19,11 +19,11 @@
 //! }
 //! ```
 //! 
-//! Debug mode outputs CPU state and disassembled code to an internal string after each execute():
+//! Execution can be traced : see the trace module. The text format outputs CPU state and disassembled code after each instruction:
 //! ```text
 //! 3E 0f     MVI A,$0f
-//! PC : 0x0003	SP : 0xff00	S : 0	Z : 0	A : 0	P : 0	C : 0
-//! B : 0x00	C : 0x00	D : 0x00	E : 0x00	H : 0x00	L : 0x00 ...
+//! PC : 0x0100    SP : 0xff00    S : 0    Z : 0    A : 0    P : 0    C : 0
+//! B : 0x00    C : 0x00    D : 0x00    E : 0x00    H : 0x00    L : 0x00 ...
 //! ```
 //! 
 //! Includes a "cpmloader" which loads and executes basic CP/M programs:
@@ -40,16 +40,44 @@
 #[doc(hidden)]
 pub mod register;
 pub mod memory;
+pub mod interrupt;
+pub mod i8259;
+pub mod run;
+pub mod scheduler;
+pub mod power;
+pub mod dma;
+pub mod step;
+pub mod trace;
+pub mod tracediff;
+pub mod vcd;
+pub mod profile;
+pub mod coverage;
+pub mod rewind;
+pub mod callstack;
+pub mod stackcheck;
+pub mod initcheck;
+pub mod smc;
+pub mod breakpoint;
 mod flags;
 mod bit;
 mod dasm;
+mod idle;
 #[cfg(test)]
 mod tests;
+#[cfg(test)]
+mod testutil;
 
 use crate::register::Registers;
 use crate::memory::Bus;
 use crate::flags::Flags;
-use std::time::SystemTime;
+use crate::interrupt::InterruptController;
+use crate::scheduler::Scheduler;
+use crate::idle::IdleDetector;
+use crate::dma::DmaDevice;
+use crate::step::{Access, AccessKind, Executed, StepKind};
+use crate::trace::{TraceEvent, TraceSink};
+pub use crate::dasm::disassemble;
+use std::{thread, time::{Duration, Instant}};
 
 const CYCLES: [u8; 256] = [
     4, 10, 7, 5, 5, 5, 7, 4, 4, 10, 7, 5, 5, 5, 7, 4,
@@ -70,13 +98,20 @@ const CYCLES: [u8; 256] = [
     5, 10, 10, 4, 11, 11, 7, 11, 5, 5, 10, 4, 11, 17, 7, 11,
 ];
 
-pub struct Debug {
-    /// Enables / Disables the debug string generation
-    pub switch: bool,
-    /// Debug for IO messages
-    pub io: bool,
-    /// The debug information string
-    pub string: String,
+// Clock cycles consumed by each execute() call while halted
+const HALT_CYCLES: u32 = 4;
+
+// Instruction length in bytes
+fn length(opcode: u8) -> u16 {
+    match opcode {
+        0x06 | 0x0E | 0x16 | 0x1E | 0x26 | 0x2E | 0x36 | 0x3E |
+        0xC6 | 0xCE | 0xD6 | 0xDE | 0xE6 | 0xEE | 0xF6 | 0xFE |
+        0xDB | 0xD3 => 2,
+        0x32 | 0x3A | 0x22 | 0x2A | 0x01 | 0x11 | 0x21 | 0x31 |
+        0xc3 | 0xDA | 0xD2 | 0xCA | 0xC2 | 0xFA | 0xF2 | 0xEA | 0xE2 |
+        0xCD | 0xDC | 0xD4 | 0xCC | 0xC4 | 0xFC | 0xF4 | 0xEC | 0xE4 => 3,
+        _ => 1,
+    }
 }
 
 pub struct CPU {
@@ -85,34 +120,52 @@ pub struct CPU {
     pub pc: u16,
     pub sp: u16,
     pub bus: Bus,
+    /// Set by HLT. The CPU only leaves the halted state when an interrupt is accepted.
     pub halt: bool,
     /// Interrupt request : true / false, instruction to execute (normally a RST command)
     pub int: (bool, u8),
     /// Interrupt enable bit
     pub inte: bool,
-    /// Outputs CPU state and disassembled code to stdout after each execute()
-    /// ```text
-    /// 3E 0f     MVI A,$0f
-    /// PC : 0x0003	SP : 0xff00	S : 0	Z : 0	A : 0	P : 0	C : 0
-    /// B : 0x00	C : 0x00	D : 0x00	E : 0x00	H : 0x00	L : 0x00 ...
-    /// ```
-    pub debug: Debug,
+    // Set by EI : interrupts are not accepted before the next instruction has been executed
+    ei_delay: bool,
+    /// Device driving the INT line and supplying the instruction bytes during interrupt acknowledge.
+    /// Unlike `int`, it can respond with a multi-byte instruction (CALL). A pending `int` request takes precedence.
+    pub int_controller: Option<Box<dyn InterruptController>>,
+    /// Device events, fired before the next instruction once the virtual clock reaches their timestamp
+    pub scheduler: Scheduler,
+    // Idle loop detection, disabled by default
+    idle: Option<IdleDetector>,
+    // Bus master devices (HOLD / HLDA)
+    dma: Vec<Box<dyn DmaDevice>>,
+    // Memory and I/O accesses of the last instruction, recorded if enabled
+    recording: bool,
+    accesses: Vec<Access>,
+    // Trace events sink, see set_tracer()
+    tracer: Option<Box<dyn TraceSink>>,
+    // Clock frequency (MHz). Defaults to 2.1 Mhz.
+    freq: f32,
+    // Virtual clock : clock cycles since creation
+    cycles: u64,
+    // Virtual clock at the last reset()
+    reset_cycles: u64,
     // Defaults to 1/60FPS = 16ms
     slice_duration: u32,
-    // Defaults to 35000 cycles per 16ms slice (2.1 Mhz).
-    // cycles = clock speed in Hz / required frames-per-second
+    // Defaults to 33600 cycles per 16ms slice (2.1 Mhz).
+    // cycles = clock speed in Hz * slice duration
     slice_max_cycles: u32,
     slice_current_cycles: u32,
-    slice_start_time: SystemTime,
+    // Real time synchronization reference : monotonic clock and virtual clock at the same instant
+    sync_instant: Instant,
+    sync_cycles: u64,
+    // Real time controls : speed multiplier, unthrottled execution, pause
+    speed: f32,
+    turbo: bool,
+    paused: bool,
 }
 
-impl Debug {
-    pub fn new() -> Debug {
-        Debug {
-            switch: false,
-            io: false,
-            string: String::new(),
-        }
+impl Default for CPU {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -128,11 +181,25 @@ impl CPU {
             halt: false,
             int: (false, 0),
             inte: false,
-            debug: Debug::new(),
+            ei_delay: false,
+            int_controller: None,
+            scheduler: Scheduler::new(),
+            idle: None,
+            dma: Vec::new(),
+            recording: false,
+            accesses: Vec::new(),
+            tracer: None,
+            freq: 2.1,
+            cycles: 0,
+            reset_cycles: 0,
             slice_duration: 16,
-            slice_max_cycles: 35000,
+            slice_max_cycles: 33600,
             slice_current_cycles: 0,
-            slice_start_time: SystemTime::now(),
+            sync_instant: Instant::now(),
+            sync_cycles: 0,
+            speed: 1.0,
+            turbo: false,
+            paused: false,
         }
     }
 
@@ -307,9 +374,9 @@ impl CPU {
 
     // XTHL Exchange stack
     fn xthl(&mut self) {
-        let pointed_by_sp = self.bus.read_word(self.sp);
+        let pointed_by_sp = self.stack_read(self.sp);
         let hl = self.reg.get_hl();
-        self.bus.write_word(self.sp, hl);
+        self.stack_write(self.sp, hl);
         self.reg.set_hl(pointed_by_sp);
     }
 
@@ -338,19 +405,103 @@ impl CPU {
     // subroutine stack push
     fn subroutine_stack_push(&mut self) {
         self.sp = self.sp.wrapping_sub(2);
-        self.bus.write_word(self.sp , self.pc.wrapping_add(3));
+        self.stack_write(self.sp, self.pc.wrapping_add(3));
     }
 
     // subroutine stack pop
     fn subroutine_stack_pop(&mut self) {
-        self.pc = self.bus.read_word(self.sp);
+        self.pc = self.stack_read(self.sp);
         self.sp = self.sp.wrapping_add(2);
     }
 
-    // interrupt stack push
-    fn interrupt_stack_push(&mut self) {
+    // RST : pushes the address of the next instruction and jumps to the restart vector
+    fn rst(&mut self, vector: u16) {
         self.sp = self.sp.wrapping_sub(2);
-        self.bus.write_word(self.sp , self.pc);
+        self.stack_write(self.sp, self.pc.wrapping_add(1));
+        self.pc = vector;
+    }
+
+    // Memory and I/O accesses of the instructions : recorded if enabled
+    fn record(&mut self, kind: AccessKind, address: u16, value: u8) {
+        let access = Access { kind, address, value };
+        if self.recording { self.accesses.push(access) }
+        self.trace(TraceEvent::Bus(access));
+    }
+
+    fn load(&mut self, kind: AccessKind, address: u16) -> u8 {
+        let value = self.bus.read_byte(address);
+        self.record(kind, address, value);
+        if !self.bus.initialized(address) { self.trace(TraceEvent::Uninitialized(Access { kind, address, value })) }
+        value
+    }
+
+    fn store(&mut self, kind: AccessKind, address: u16, value: u8) {
+        self.bus.write_byte(address, value);
+        self.record(kind, address, value);
+    }
+
+    fn read_byte(&mut self, address: u16) -> u8 {
+        self.load(AccessKind::Read, address)
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        self.store(AccessKind::Write, address, value)
+    }
+
+    fn read_word(&mut self, address: u16) -> u16 {
+        u16::from_le_bytes([self.read_byte(address), self.read_byte(address.wrapping_add(1))])
+    }
+
+    fn write_word(&mut self, address: u16, value: u16) {
+        let [l, h] = value.to_le_bytes();
+        self.write_byte(address, l);
+        self.write_byte(address.wrapping_add(1), h);
+    }
+
+    fn stack_read(&mut self, address: u16) -> u16 {
+        u16::from_le_bytes([self.load(AccessKind::StackRead, address), self.load(AccessKind::StackRead, address.wrapping_add(1))])
+    }
+
+    // The high byte is written first, like PUSH and CALL do
+    fn stack_write(&mut self, address: u16, value: u16) {
+        let [l, h] = value.to_le_bytes();
+        self.store(AccessKind::StackWrite, address.wrapping_add(1), h);
+        self.store(AccessKind::StackWrite, address, l);
+    }
+
+    fn io_read(&mut self, port: u8) -> Option<u8> {
+        let value = self.bus.io_read(port);
+        self.record(AccessKind::In, u16::from(port), value.unwrap_or(self.reg.a));
+        self.trace(TraceEvent::In { port, value: value.unwrap_or(self.reg.a) });
+        if let Some(d) = self.idle.as_mut() { d.inputs.push((port, value.unwrap_or(self.reg.a))) }
+        value
+    }
+
+    fn io_write(&mut self, port: u8, value: u8) {
+        self.bus.io_write(port, value);
+        self.record(AccessKind::Out, u16::from(port), value);
+        self.trace(TraceEvent::Out { port, value });
+    }
+
+    // Is the INT line asserted ?
+    fn int_pending(&self) -> bool {
+        self.int.0 || self.int_controller.as_ref().is_some_and(|i| i.int())
+    }
+
+    // Can a pending interrupt be accepted before the next instruction ?
+    fn int_accepted(&self) -> bool {
+        self.inte && !self.ei_delay && self.int_pending()
+    }
+
+    // Interrupt acknowledge : returns the instruction byte supplied during INTA cycle n
+    fn int_ack(&mut self, n: u8) -> u8 {
+        if self.int.0 {
+            return if n == 0 { self.int.1 } else { 0 };
+        }
+        match self.int_controller.as_mut() {
+            Some(i) => i.inta(n),
+            None => 0,
+        }
     }
 
     /// Sets CPU frequency (MHz)
@@ -360,53 +511,201 @@ impl CPU {
     /// c.set_freq(1.7);            // CPU will run at 1.7 Mhz
     /// ```
     pub fn set_freq(&mut self, f: f32) {
-        let cycles = (f * 1000000 as f32) / (1000/self.slice_duration) as f32;
+        self.freq = f;
+        self.set_slice_duration(self.slice_duration);
+    }
+
+    /// Sets the duration (ms) of emulated time between two real time synchronizations. Defaults to 16ms.
+    /// Shorter slices give a smoother execution, longer slices a lower overhead.
+    pub fn set_slice_duration(&mut self, ms: u32) {
+        self.slice_duration = ms.max(1);
+        let cycles = (self.freq * 1000.0) * self.slice_duration as f32;
         self.slice_max_cycles = cycles as u32;
+        self.resync();
+    }
+
+    /// Sets the real time speed multiplier : 0.5 runs at half speed, 2.0 at twice the speed. Defaults to 1.0.
+    /// Only affects execute_timed() and execute_throttled() : the virtual clock is unchanged.
+    pub fn set_speed(&mut self, multiplier: f32) {
+        if multiplier > 0.0 { self.speed = multiplier }
+        self.resync();
+    }
+
+    /// Turbo mode : execute_timed() and execute_throttled() run as fast as possible.
+    pub fn set_turbo(&mut self, turbo: bool) {
+        self.turbo = turbo;
+        self.resync();
+    }
+
+    /// Pauses real time execution : execute_timed() and execute_throttled() no longer execute instructions.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Resumes real time execution.
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.resync();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Returns the CPU frequency (MHz)
+    pub fn freq(&self) -> f32 {
+        self.freq
+    }
+
+    /// Returns the number of clock cycles elapsed since the CPU was created (virtual clock). Unlike real time,
+    /// it only depends on the executed code : runs are reproducible. Like the hardware clock, it keeps running through reset() :
+    /// see cycles_since_reset().
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Returns the number of clock cycles elapsed since the last reset(), or since the CPU was created.
+    /// ```rust
+    /// use intel8080::CPU;
+    /// let mut c = CPU::new();
+    /// c.execute();                        // NOP
+    /// c.reset();
+    /// c.execute();
+    /// assert_eq!((c.cycles(), c.cycles_since_reset()), (8, 4));
+    /// ```
+    pub fn cycles_since_reset(&self) -> u64 {
+        self.cycles.saturating_sub(self.reset_cycles)
+    }
+
+    /// Converts the virtual clock to emulated time at the configured frequency.
+    /// ```rust
+    /// use intel8080::CPU;
+    /// let mut c = CPU::new();
+    /// c.set_freq(2.0);
+    /// c.bus.write_byte(0x0000, 0xc3);     // JMP $0000
+    /// for _ in 0..200 { c.execute(); }    // 200 * 10 cycles
+    /// assert_eq!(c.emulated_time().as_micros(), 1000);
+    /// ```
+    pub fn emulated_time(&self) -> Duration {
+        self.cycles_to_duration(self.cycles)
+    }
+
+    fn cycles_to_duration(&self, cycles: u64) -> Duration {
+        Duration::from_nanos((cycles as f64 * 1000.0 / self.freq as f64) as u64)
     }
 
-    /// Fetches and executes one instruction from (pc). Returns the sleep time when slice_max_cycles is reached.
+    // Real time synchronization starts again from now
+    fn resync(&mut self) {
+        self.sync_instant = Instant::now();
+        self.sync_cycles = self.cycles;
+        self.slice_current_cycles = 0;
+    }
+
+    /// Fetches and executes one instruction from (pc). Returns the sleep time (ms) when slice_max_cycles is reached.
+    /// The sleep time is computed from a monotonic clock : it brings real time back in line with
+    /// emulated time since the start of execution, so the errors of the caller's sleeps do not accumulate.
+    /// When paused, no instruction is executed and the slice duration is returned.
     pub fn execute_timed(&mut self) -> Option<u32> {
+        if self.paused { return Some(self.slice_duration) }
         let mut sleep_time: Option<u32> = None;
+        // Turbo : the slices only bound the idle fast-forward, there is no sleep
         if self.slice_current_cycles > self.slice_max_cycles {
             self.slice_current_cycles = 0;
-            // d = time taken to execute the slice_max_cycles
-            if let Ok(d) = self.slice_start_time.elapsed() {
-                sleep_time = Some(self.slice_duration.saturating_sub(d.as_millis() as u32));
-                self.slice_start_time = SystemTime::now();
+            if !self.turbo {
+                let emulated = self.cycles_to_duration(self.cycles - self.sync_cycles).div_f32(self.speed);
+                let real = self.sync_instant.elapsed();
+                match emulated.checked_sub(real) {
+                    Some(d) => sleep_time = Some(d.as_millis() as u32),
+                    None => {
+                        sleep_time = Some(0);
+                        // Too late (slow host, suspended process) : we don't try to catch up
+                        if real - emulated > Duration::from_millis(u64::from(self.slice_duration) * 4) { self.resync() }
+                    }
+                }
             }
         }
+        // Idle loops are not fast-forwarded beyond the current slice
+        let limit = self.set_idle_limit(self.cycles + u64::from(self.slice_max_cycles.saturating_sub(self.slice_current_cycles)));
         let cycles = self.execute();
+        self.set_idle_limit(limit);
         self.slice_current_cycles += cycles;
         sleep_time
     }
 
+    /// Fetches and executes one instruction from (pc) at real time speed, sleeping when needed.
+    /// Returns the number of consumed clock cycles (0 when paused).
+    /// ```rust
+    /// use intel8080::CPU;
+    /// let mut c = CPU::new();
+    /// c.set_speed(2.0);                   // 4.2 Mhz
+    /// c.bus.write_byte(0x0000, 0xc3);     // JMP $0000
+    /// while c.cycles() < 10000 { c.execute_throttled(); }
+    /// ```
+    pub fn execute_throttled(&mut self) -> u32 {
+        let cycles = self.cycles;
+        if let Some(t) = self.execute_timed() {
+            if t > 0 { thread::sleep(Duration::from_millis(u64::from(t))) }
+        }
+        (self.cycles - cycles) as u32
+    }
+
     /// Fetches and executes one instruction from (pc). Returns the number of consumed clock cycles. No execution speed limit.
+    /// While halted, no instruction is executed and 4 clock cycles are returned, until an interrupt is accepted.
     pub fn execute(&mut self) -> u32 {
-        if self.halt { return 0 };
-        
-        // Saving current PC for debug output
-        let pc = self.pc;
-
-        let opcode = match self.inte {
-            false => self.bus.read_byte(self.pc),
-            // interrupts enabled : is there a pending interrupt ?
-            true => match self.int.0 {
-                false => self.bus.read_byte(self.pc),
-                true => self.int.1,
-            }
-        };
+        self.exec().cycles
+    }
 
-        let mut cycles = CYCLES[opcode as usize].into();
+    // Executes one instruction, or waits one cycle slot while halted
+    fn exec(&mut self) -> Executed {
+        if self.recording { self.accesses.clear() }
+        self.fire_events();
+
+        // HOLD : the CPU is stalled while DMA devices use the bus
+        let stall = if self.dma.is_empty() { 0 } else { self.dma_transfers() };
+
+        // Halted : the CPU keeps running idle cycles until an interrupt is accepted
+        if self.halt {
+            if !self.int_accepted() {
+                let cycles = (self.idle_halt() as u32).max(HALT_CYCLES) + stall;
+                self.cycles += u64::from(cycles);
+                return Executed { address: self.pc, bytes: [0; 3], cycles, kind: StepKind::Halted }
+            };
+            self.halt = false;
+        }
         
-        // if opcode is RST : is it called via an interrupt, or via the program ?
-        let direct_rst = if self.inte && self.int.0 { false } else { true };
+        // Saving current PC and virtual clock for the trace
+        let (pc, clock) = (self.pc, self.cycles + u64::from(stall));
 
-        // interrupts enable and pending interrupt : we disable interrupts and clear interrupt request
-        if self.inte && self.int.0 {
+        // Instruction bytes : read from memory, or supplied by the interrupting device
+        let mut bytes = [0u8; 3];
+
+        // interrupts enabled and pending interrupt : we disable interrupts and run the acknowledge cycles
+        let interrupt = self.int_accepted();
+        self.ei_delay = false;
+        if interrupt {
             self.inte = false;
+            bytes[0] = self.int_ack(0);
+            let len = length(bytes[0]);
+            for (n, b) in bytes.iter_mut().enumerate().take(usize::from(len)).skip(1) {
+                *b = self.int_ack(n as u8);
+            }
             self.int = (false, 0);
+            self.trace(TraceEvent::Interrupt { address: pc, bytes });
+            // The instruction has not been fetched from memory : pc must not advance past it
+            self.pc = self.pc.wrapping_sub(len);
+        } else {
+            bytes[0] = self.load(AccessKind::Fetch, self.pc);
+            for n in 1..length(bytes[0]) {
+                bytes[usize::from(n)] = self.load(AccessKind::Fetch, self.pc.wrapping_add(n));
+            }
         }
 
+        let opcode = bytes[0];
+        let d8 = bytes[1];
+        let d16 = u16::from_le_bytes([bytes[1], bytes[2]]);
+
+        let mut cycles = u32::from(CYCLES[opcode as usize]) + stall;
+
         match opcode {
             /* Carry bit instructions */
             0x3f => self.flags.c = !self.flags.c,                           // CMC
@@ -423,8 +722,9 @@ impl CPU {
             0x3C => self.reg.a = self.inr(self.reg.a),          // INR A
             0x34 => {                                                       // INR (HL)
                 let addr = self.reg.get_hl();
-                let r = self.inr(self.bus.read_byte(addr));
-                self.bus.write_byte(addr, r);
+                let n = self.read_byte(addr);
+                let r = self.inr(n);
+                self.write_byte(addr, r);
             },
 
             // DCR Decrement Register or Memory
@@ -437,8 +737,9 @@ impl CPU {
             0x3D => self.reg.a = self.dcr(self.reg.a),          // DCR A
             0x35 => {                                                       // DCR (HL)
                 let addr = self.reg.get_hl();
-                let r = self.dcr(self.bus.read_byte(addr));
-                self.bus.write_byte(addr, r);
+                let n = self.read_byte(addr);
+                let r = self.dcr(n);
+                self.write_byte(addr, r);
             },
 
             // CMA Complement Accumulator
@@ -459,7 +760,7 @@ impl CPU {
             0x45 => self.reg.b = self.reg.l,                    // MOV B,L
             0x46 => {                                                       // MOV B,(HL)
                 let addr = self.reg.get_hl();
-                self.reg.b = self.bus.read_byte(addr)
+                self.reg.b = self.read_byte(addr)
             },
             0x47 => self.reg.b = self.reg.a,                    // MOV B,A
 
@@ -471,7 +772,7 @@ impl CPU {
             0x4D => self.reg.c = self.reg.l,                    // MOV C,L
             0x4E => {                                                       // MOV C,(HL)
                 let addr = self.reg.get_hl();
-                self.reg.c = self.bus.read_byte(addr)
+                self.reg.c = self.read_byte(addr)
             },
             0x4F => self.reg.c = self.reg.a,                    // MOV C,A
 
@@ -483,7 +784,7 @@ impl CPU {
             0x55 => self.reg.d = self.reg.l,                    // MOV D,L
             0x56 => {                                                       // MOV D,(HL)
                 let addr = self.reg.get_hl();
-                self.reg.d = self.bus.read_byte(addr)
+                self.reg.d = self.read_byte(addr)
             },
             0x57 => self.reg.d = self.reg.a,                    // MOV D,A
 
@@ -495,7 +796,7 @@ impl CPU {
             0x5D => self.reg.e = self.reg.l,                    // MOV E,L
             0x5E => {                                                       // MOV E,(HL)
                 let addr = self.reg.get_hl();
-                self.reg.e = self.bus.read_byte(addr)
+                self.reg.e = self.read_byte(addr)
             },
             0x5F => self.reg.e = self.reg.a,                    // MOV E,A
 
@@ -507,7 +808,7 @@ impl CPU {
             0x65 => self.reg.h = self.reg.l,                    // MOV H,L
             0x66 => {                                                       // MOV H,(HL)
                 let addr = self.reg.get_hl();
-                self.reg.h = self.bus.read_byte(addr)
+                self.reg.h = self.read_byte(addr)
             },
             0x67 => self.reg.h = self.reg.a,                    // MOV H,A
 
@@ -519,40 +820,43 @@ impl CPU {
             0x6D => {},                                                     // MOV L,L
             0x6E => {                                                       // MOV L,(HL)
                 let addr = self.reg.get_hl();
-                self.reg.l = self.bus.read_byte(addr)
+                self.reg.l = self.read_byte(addr)
             },
             0x6F => self.reg.l = self.reg.a,                    // MOV L,A
 
             0x70 => {                                                       // MOV (HL), B
                 let addr = self.reg.get_hl();
-                self.bus.write_byte(addr, self.reg.b)
+                self.write_byte(addr, self.reg.b)
             },
             0x71 => {                                                       // MOV (HL), C
                 let addr = self.reg.get_hl();
-                self.bus.write_byte(addr, self.reg.c)
+                self.write_byte(addr, self.reg.c)
             },
             0x72 => {                                                       // MOV (HL), D
                 let addr = self.reg.get_hl();
-                self.bus.write_byte(addr, self.reg.d)
+                self.write_byte(addr, self.reg.d)
             },
             0x73 => {                                                       // MOV (HL), E
                 let addr = self.reg.get_hl();
-                self.bus.write_byte(addr, self.reg.e)
+                self.write_byte(addr, self.reg.e)
             },
             0x74 => {                                                       // MOV (HL), H
                 let addr = self.reg.get_hl();
-                self.bus.write_byte(addr, self.reg.h)
+                self.write_byte(addr, self.reg.h)
             },
             0x75 => {                                                       // MOV (HL), L
                 let addr = self.reg.get_hl();
-                self.bus.write_byte(addr, self.reg.l)
+                self.write_byte(addr, self.reg.l)
             },
 
-            0x76 => self.halt = true,                                       // HLT
+            0x76 => {                                                       // HLT
+                self.halt = true;
+                self.trace(TraceEvent::Halt { address: pc });
+            },
 
             0x77 => {                                                       // MOV (HL), A
                 let addr = self.reg.get_hl();
-                self.bus.write_byte(addr, self.reg.a)
+                self.write_byte(addr, self.reg.a)
             },
 
             0x78 => self.reg.a = self.reg.b,                    // MOV A,B                                                     // MOV B,B
@@ -563,28 +867,28 @@ impl CPU {
             0x7D => self.reg.a = self.reg.l,                    // MOV A,L
             0x7E => {                                                       // MOV A,(HL)
                 let addr = self.reg.get_hl();
-                self.reg.a = self.bus.read_byte(addr)
+                self.reg.a = self.read_byte(addr)
             },
             0x7F => {},                                                     // MOV A,A
 
             // STAX Store accumulator
             0x02 => {                                                       // STAX B
                 let addr = self.reg.get_bc();
-                self.bus.write_byte(addr, self.reg.a)
+                self.write_byte(addr, self.reg.a)
             }
             0x12 => {                                                       // STAX D
                 let addr = self.reg.get_de();
-                self.bus.write_byte(addr, self.reg.a)
+                self.write_byte(addr, self.reg.a)
             },
 
             // LDAX Load accumulator
             0x0A => {                                                       // LDAX B
                 let addr = self.reg.get_bc();
-                self.reg.a = self.bus.read_byte(addr)
+                self.reg.a = self.read_byte(addr)
             },
             0x1A => {                                                       // LDAX D
                 let addr = self.reg.get_de();
-                self.reg.a = self.bus.read_byte(addr)
+                self.reg.a = self.read_byte(addr)
             },
 
             /* Register or Memory to Accumulator instructions*/
@@ -597,7 +901,7 @@ impl CPU {
             0x85 => self.add(self.reg.l),                             // ADD L
             0x86 => {                                                       // ADD (HL)
                 let addr = self.reg.get_hl();
-                let n = self.bus.read_byte(addr);
+                let n = self.read_byte(addr);
                 self.add(n)
             },
             0x87 => self.add(self.reg.a),                             // ADD A
@@ -611,7 +915,7 @@ impl CPU {
             0x8D => self.adc(self.reg.l),                             // ADC L
             0x8E => {                                                       // ADC (HL)
                 let addr = self.reg.get_hl();
-                let n = self.bus.read_byte(addr);
+                let n = self.read_byte(addr);
                 self.adc(n)
             },
             0x8F => self.adc(self.reg.a),                             // ADC A
@@ -625,7 +929,7 @@ impl CPU {
             0x95 => self.sub(self.reg.l),                             // SUB L
             0x96 => {                                                       // SUB (HL)
                 let addr = self.reg.get_hl();
-                let n = self.bus.read_byte(addr);
+                let n = self.read_byte(addr);
                 self.sub(n)
             },
             0x97 => self.sub(self.reg.a),                             // SUB A
@@ -639,7 +943,7 @@ impl CPU {
             0x9D => self.sbb(self.reg.l),                             // SBB L
             0x9E => {                                                       // SBB (HL)
                 let addr = self.reg.get_hl();
-                let n = self.bus.read_byte(addr);
+                let n = self.read_byte(addr);
                 self.sbb(n)
             },
             0x9F => self.sbb(self.reg.a),                             // SBB A
@@ -653,7 +957,7 @@ impl CPU {
             0xA5 => self.ana(self.reg.l),                             // ANA L
             0xA6 => {                                                       // ANA (HL)
                 let addr = self.reg.get_hl();
-                let n = self.bus.read_byte(addr);
+                let n = self.read_byte(addr);
                 self.ana(n)
             },
             0xA7 => self.ana(self.reg.a),                             // ANA A
@@ -667,7 +971,7 @@ impl CPU {
             0xAD => self.xra(self.reg.l),                             // XRA L
             0xAE => {                                                       // XNA (HL)
                 let addr = self.reg.get_hl();
-                let n = self.bus.read_byte(addr);
+                let n = self.read_byte(addr);
                 self.xra(n)
             },
             0xAF => self.xra(self.reg.a),                             // XRA A
@@ -681,7 +985,7 @@ impl CPU {
             0xB5 => self.ora(self.reg.l),                             // ORA L
             0xB6 => {                                                       // ORA (HL)
                 let addr = self.reg.get_hl();
-                let n = self.bus.read_byte(addr);
+                let n = self.read_byte(addr);
                 self.ora(n)
             },
             0xB7 => self.ora(self.reg.a),                             // ORA A
@@ -695,7 +999,7 @@ impl CPU {
             0xBD => self.cmp(self.reg.l),                             // CMP L
             0xBE => {                                                       // CMP (HL)
                 let addr = self.reg.get_hl();
-                let n = self.bus.read_byte(addr);
+                let n = self.read_byte(addr);
                 self.cmp(n)
             },
             0xBF => self.cmp(self.reg.a),                             // CMP A
@@ -710,41 +1014,44 @@ impl CPU {
             // PUSH data onto stack
             0xC5 => {                                                       // PUSH B
                 self.sp = self.sp.wrapping_sub(2);
-                self.bus.write_word(self.sp, self.reg.get_bc());
+                self.stack_write(self.sp, self.reg.get_bc());
             },
             0xD5 => {                                                       // PUSH D
                 self.sp = self.sp.wrapping_sub(2);
-                self.bus.write_word(self.sp, self.reg.get_de());
+                self.stack_write(self.sp, self.reg.get_de());
             },
             0xE5 => {                                                       // PUSH H
                 self.sp = self.sp.wrapping_sub(2);
-                self.bus.write_word(self.sp, self.reg.get_hl());
+                self.stack_write(self.sp, self.reg.get_hl());
             },
             0xF5 => {                                                       // PUSH PSW
                 self.sp = self.sp.wrapping_sub(2);
-                self.bus.write_byte(self.sp, self.flags.as_byte());
-                self.bus.write_byte(self.sp + 1, self.reg.a);
+                self.store(AccessKind::StackWrite, self.sp.wrapping_add(1), self.reg.a);
+                self.store(AccessKind::StackWrite, self.sp, self.flags.as_byte());
             },
 
             // POP data off stack
             0xC1 => {                                                       // POP B
-                self.reg.set_bc(self.bus.read_word(self.sp));
+                let d = self.stack_read(self.sp);
+                self.reg.set_bc(d);
                 self.sp = self.sp.wrapping_add(2);
             },
 
             0xD1 => {                                                       // POP D
-                self.reg.set_de(self.bus.read_word(self.sp));
+                let d = self.stack_read(self.sp);
+                self.reg.set_de(d);
                 self.sp = self.sp.wrapping_add(2);
             },
 
             0xE1 => {                                                       // POP H
-                self.reg.set_hl(self.bus.read_word(self.sp));
+                let d = self.stack_read(self.sp);
+                self.reg.set_hl(d);
                 self.sp = self.sp.wrapping_add(2);
             },
 
             0xF1 => {                                                       // POP PSW
-                self.reg.a = self.bus.read_byte((self.sp)+1);
-                let bflags = self.bus.read_byte(self.sp);
+                let bflags = self.load(AccessKind::StackRead, self.sp);
+                self.reg.a = self.load(AccessKind::StackRead, self.sp.wrapping_add(1));
                 self.flags.from_byte(bflags);
                 self.sp = self.sp.wrapping_add(2);
             },
@@ -821,128 +1128,104 @@ impl CPU {
             /* Immediate instructions */
             // LXI Move immediate data
             0x01 => {                                                       // LXI B
-                let d16 = self.bus.read_word(self.pc + 1); 
                 self.reg.set_bc(d16);
             },
             0x11 => {                                                       // LXI D
-                let d16 = self.bus.read_word(self.pc + 1); 
                 self.reg.set_de(d16);
             },
             0x21 => {                                                       // LXI H
-                let d16 = self.bus.read_word(self.pc + 1); 
                 self.reg.set_hl(d16);
             },
             0x31 => {                                                       // LXI SP
-                let d16 = self.bus.read_word(self.pc + 1); 
                 self.sp = d16;
             },
             // MVI Move immediate data
             0x06 => {                                                       // MVI B,d8
-                let d8 = self.bus.read_byte(self.pc + 1);
                 self.reg.b = d8;
             },
             0x0E => {                                                       // MVI C,d8
-                let d8 = self.bus.read_byte(self.pc + 1);
                 self.reg.c = d8;
             },
             0x16 => {                                                       // MVI D,d8
-                let d8 = self.bus.read_byte(self.pc + 1);
                 self.reg.d = d8;
             },
             0x1E => {                                                       // MVI E,d8
-                let d8 = self.bus.read_byte(self.pc + 1);
                 self.reg.e = d8;
             },
             0x26 => {                                                       // MVI H,d8
-                let d8 = self.bus.read_byte(self.pc + 1);
                 self.reg.h = d8;
             },
             0x2E => {                                                       // MVI L,d8
-                let d8 = self.bus.read_byte(self.pc + 1);
                 self.reg.l = d8;
             },
             0x36 => {                                                       // MVI (HL),d8
-                let d8 = self.bus.read_byte(self.pc + 1);
                 let addr = self.reg.get_hl();
-                self.bus.write_byte(addr, d8);
+                self.write_byte(addr, d8);
             },
             0x3E => {                                                       // MVI A,d8
-                let d8 = self.bus.read_byte(self.pc + 1);
                 self.reg.a = d8;
             },
 
             // ADI add immediate to accumulator
             0xC6 => {                                                       // ADI
-                let n = self.bus.read_byte(self.pc + 1);
-                self.add(n);
+                self.add(d8);
             },
 
             // ACI add immediate to accumulator with carry
             0xCE => {                                                       // ACI
-                let n = self.bus.read_byte(self.pc + 1);
-                self.adc(n);
+                self.adc(d8);
             },
 
             // SUI substract immediate from accumulator
             0xD6 => {                                                       // SUI
-                let n = self.bus.read_byte(self.pc + 1);
-                self.sub(n);
+                self.sub(d8);
             },
 
             // SBI substract immediate from accumulator with borrow
             0xDE => {                                                       // SBI
-                let n = self.bus.read_byte(self.pc + 1);
-                self.sbb(n);
+                self.sbb(d8);
             },
 
             // ANI and immediate with accumulator
             0xE6 => {                                                       // ANI
-                let n = self.bus.read_byte(self.pc + 1);
-                self.ana(n);
+                self.ana(d8);
             },
 
             // XRI exclusive-or immediate with accumulator
             0xEE => {                                                       // XRI
-                let n = self.bus.read_byte(self.pc + 1);
-                self.xra(n);
+                self.xra(d8);
             },
 
             // ORI or immediate with accumulator
             0xF6 => {                                                       // ORI
-                let n = self.bus.read_byte(self.pc + 1);
-                self.ora(n);
+                self.ora(d8);
             },
 
             // CPI compare immediate with accumulator
             0xFE => {                                                       // CPI
-                let n = self.bus.read_byte(self.pc + 1);
-                self.cmp(n);
+                self.cmp(d8);
             },
 
             /* Direct addressing instructions */
             // STA Store accumulator direct
             0x32 => {                                                       // STA
-                let addr = self.bus.read_word(self.pc + 1);
-                self.bus.write_byte(addr, self.reg.a);
+                self.write_byte(d16, self.reg.a);
             },
 
             // LDA Store accumulator direct
             0x3A => {                                                       // LDA
-                let addr = self.bus.read_word(self.pc + 1);
-                self.reg.a = self.bus.read_byte(addr);
+                self.reg.a = self.read_byte(d16);
             },
 
             // SHLD Store H and L direct
             0x22 => {                                                       // SHLD
                 let d = self.reg.get_hl();
-                let addr = self.bus.read_word(self.pc + 1);
-                self.bus.write_word(addr, d);
+                self.write_word(d16, d);
             },
 
             // LHLD Load H and L direct
             0x2A => {                                                       // LHLD
-                let addr = self.bus.read_word(self.pc + 1);
-                let d = self.bus.read_word(addr);
+                let d = self.read_word(d16);
                 self.reg.set_hl(d);
             },
 
@@ -951,254 +1234,190 @@ impl CPU {
             0xE9 => { self.pc = self.reg.get_hl(); },                 // PCHL
             // JMP Jump
             0xC3 => {                                                       // JMP
-                let addr = self.bus.read_word(self.pc + 1);
-                self.pc = addr;
+                self.pc = d16;
             },
             // JC Jump if carry
             0xDA => {                                                       // JC
-                let addr = self.bus.read_word(self.pc + 1);
-                if self.flags.c { self.pc = addr; } else { self.pc += 3 }
+                if self.flags.c { self.pc = d16; } else { self.pc = self.pc.wrapping_add(3) }
             },
             // JNC Jump if no carry
             0xD2 => {                                                       // JNC
-                let addr = self.bus.read_word(self.pc + 1);
-                if !self.flags.c { self.pc = addr; } else { self.pc += 3 }
+                if !self.flags.c { self.pc = d16; } else { self.pc = self.pc.wrapping_add(3) }
             },
             // JZ Jump if zero
             0xCA => {                                                       // JZ
-                let addr = self.bus.read_word(self.pc + 1);
-                if self.flags.z { self.pc = addr; } else { self.pc += 3 }
+                if self.flags.z { self.pc = d16; } else { self.pc = self.pc.wrapping_add(3) }
             },
             // JNZ Jump if not zero
             0xC2 => {                                                       // JNZ
-                let addr = self.bus.read_word(self.pc + 1);
-                if !self.flags.z { self.pc = addr; } else { self.pc += 3 }
+                if !self.flags.z { self.pc = d16; } else { self.pc = self.pc.wrapping_add(3) }
             },
             // JM Jump if minus
             0xFA => {                                                       // JM
-                let addr = self.bus.read_word(self.pc + 1);
-                if self.flags.s { self.pc = addr; } else { self.pc += 3 }
+                if self.flags.s { self.pc = d16; } else { self.pc = self.pc.wrapping_add(3) }
             },
             // JP Jump if positive
             0xF2 => {                                                       // JP
-                let addr = self.bus.read_word(self.pc + 1);
-                if !self.flags.s { self.pc = addr; } else { self.pc += 3 }
+                if !self.flags.s { self.pc = d16; } else { self.pc = self.pc.wrapping_add(3) }
             },
             // JPE Jump if parity even
             0xEA => {                                                       // JPE
-                let addr = self.bus.read_word(self.pc + 1);
-                if self.flags.p { self.pc = addr; } else { self.pc += 3 }
+                if self.flags.p { self.pc = d16; } else { self.pc = self.pc.wrapping_add(3) }
             },
             // JPO Jump if parity odd
             0xE2 => {                                                       // JPO
-                let addr = self.bus.read_word(self.pc + 1);
-                if !self.flags.p { self.pc = addr; } else { self.pc += 3 }
+                if !self.flags.p { self.pc = d16; } else { self.pc = self.pc.wrapping_add(3) }
             },
 
             /* Call subroutine instructions */
             // CALL
             0xCD => {                                                       // CALL
-                let addr = self.bus.read_word(self.pc + 1);
                 self.subroutine_stack_push();
-                self.pc = addr;
+                self.pc = d16;
             },
             // CC Call if carry
             0xDC => {                                                       // CC
-                let addr = self.bus.read_word(self.pc + 1);
                 if self.flags.c {
                     self.subroutine_stack_push();
-                    self.pc = addr;
-                } else { self.pc += 3 }
+                    self.pc = d16;
+                } else { self.pc = self.pc.wrapping_add(3) }
             },
             // CNC Call if no carry
             0xD4 => {                                                       // CNC
-                let addr = self.bus.read_word(self.pc + 1);
                 if !self.flags.c {
                     self.subroutine_stack_push();
-                    self.pc = addr;
+                    self.pc = d16;
                     cycles += 6;
-                } else { self.pc += 3 }
+                } else { self.pc = self.pc.wrapping_add(3) }
             },
             // CZ Call if zero
             0xCC => {                                                       // CZ
-                let addr = self.bus.read_word(self.pc + 1);
                 if self.flags.z {
                     self.subroutine_stack_push();
-                    self.pc = addr;
+                    self.pc = d16;
                     cycles += 6;
-                } else { self.pc += 3 }
+                } else { self.pc = self.pc.wrapping_add(3) }
             },
             // CNZ Call if not zero
             0xC4 => {                                                       // CNZ
-                let addr = self.bus.read_word(self.pc + 1);
                 if !self.flags.z {
                     self.subroutine_stack_push();
-                    self.pc = addr;
+                    self.pc = d16;
                     cycles += 6;
-                 } else { self.pc += 3 }
+                 } else { self.pc = self.pc.wrapping_add(3) }
             },
             // CM Call if minus
             0xFC => {                                                       // CM
-                let addr = self.bus.read_word(self.pc + 1);
                 if self.flags.s {
                     self.subroutine_stack_push();
-                    self.pc = addr;
+                    self.pc = d16;
                     cycles += 6;
-                } else { self.pc += 3 }
+                } else { self.pc = self.pc.wrapping_add(3) }
             },
             // CP Call if plus
             0xF4 => {                                                       // CP
-                let addr = self.bus.read_word(self.pc + 1);
                 if !self.flags.s {
                     self.subroutine_stack_push();
-                    self.pc = addr;
+                    self.pc = d16;
                     cycles += 6;
-                } else { self.pc += 3 }
+                } else { self.pc = self.pc.wrapping_add(3) }
             },
             // CPE Call if parity even
             0xEC => {                                                       // CPE
-                let addr = self.bus.read_word(self.pc + 1);
                 if self.flags.p {
                     self.subroutine_stack_push();
-                    self.pc = addr;
+                    self.pc = d16;
                     cycles += 6;
-                } else { self.pc += 3 }
+                } else { self.pc = self.pc.wrapping_add(3) }
             },
             // CPO Call if parity odd
             0xE4 => {                                                       // CPO
-                let addr = self.bus.read_word(self.pc + 1);
                 if !self.flags.p {
                     self.subroutine_stack_push();
-                    self.pc = addr;
+                    self.pc = d16;
                     cycles += 6;
-                } else { self.pc += 3 }
+                } else { self.pc = self.pc.wrapping_add(3) }
             },
 
             /* Return from subroutine instructions */
             // RET Return
             0xC9 => self.subroutine_stack_pop(),                                                    // RET
             // RC Return if carry
-            0xD8 => if self.flags.c { self.subroutine_stack_pop(); cycles += 6; } else { self.pc +=1; },         // RC
+            0xD8 => if self.flags.c { self.subroutine_stack_pop(); cycles += 6; } else { self.pc = self.pc.wrapping_add(1); },         // RC
             // RNC Return if no carry
-            0xD0 => if !self.flags.c { self.subroutine_stack_pop(); cycles += 6; } else { self.pc +=1; },        // RNC
+            0xD0 => if !self.flags.c { self.subroutine_stack_pop(); cycles += 6; } else { self.pc = self.pc.wrapping_add(1); },        // RNC
             // RZ Return if zero
-            0xC8 => if self.flags.z { self.subroutine_stack_pop(); cycles += 6; } else { self.pc +=1; },         // RZ
+            0xC8 => if self.flags.z { self.subroutine_stack_pop(); cycles += 6; } else { self.pc = self.pc.wrapping_add(1); },         // RZ
             // RNZ Return if not zero
-            0xC0 => if !self.flags.z { self.subroutine_stack_pop(); cycles += 6; } else { self.pc +=1; },        // RNZ
+            0xC0 => if !self.flags.z { self.subroutine_stack_pop(); cycles += 6; } else { self.pc = self.pc.wrapping_add(1); },        // RNZ
             // RM Return if minus
-            0xF8 => if self.flags.s { self.subroutine_stack_pop(); cycles += 6; } else { self.pc +=1; },         // RM
+            0xF8 => if self.flags.s { self.subroutine_stack_pop(); cycles += 6; } else { self.pc = self.pc.wrapping_add(1); },         // RM
             // RP Return if plus
-            0xF0 => if !self.flags.s { self.subroutine_stack_pop(); cycles += 6; } else { self.pc +=1; },        // RP
+            0xF0 => if !self.flags.s { self.subroutine_stack_pop(); cycles += 6; } else { self.pc = self.pc.wrapping_add(1); },        // RP
             // RPE Return if parity even
-            0xE8 => if self.flags.p { self.subroutine_stack_pop(); cycles += 6; } else { self.pc +=1; },         // RPE
+            0xE8 => if self.flags.p { self.subroutine_stack_pop(); cycles += 6; } else { self.pc = self.pc.wrapping_add(1); },         // RPE
             // RPO Return if parity odd
-            0xE0 => if !self.flags.p { self.subroutine_stack_pop(); cycles += 6; } else { self.pc +=1; },        // RPO
+            0xE0 => if !self.flags.p { self.subroutine_stack_pop(); cycles += 6; } else { self.pc = self.pc.wrapping_add(1); },        // RPO
 
             /* Interrupt flip-flop instructions */
-            // EI Enable interrupts
-            0xFB => self.inte = true,
-            // DI Disable Interrupts
-            0xF3 => self.inte = false,
-
-            /* RST (Restart) instructions */
-            0xC7 => {                                                       // RST 0
-                match direct_rst {
-                    false => self.interrupt_stack_push(),
-                    true => { self.pc +=1; self.interrupt_stack_push(); }
-                }
-                self.pc = 0x0000;
-            },
-
-            0xCF => {                                                       // RST 1
-                match direct_rst {
-                    false => self.interrupt_stack_push(),
-                    true => { self.pc +=1; self.interrupt_stack_push(); }
-                }
-                self.pc = 0x0008;
-            },
-
-            0xD7 => {                                                       // RST 2
-                match direct_rst {
-                    false => self.interrupt_stack_push(),
-                    true => { self.pc +=1; self.interrupt_stack_push(); }
-                }
-                self.pc = 0x0010;
-            },
-
-            0xDF => {                                                       // RST 3
-                match direct_rst {
-                    false => self.interrupt_stack_push(),
-                    true => { self.pc +=1; self.interrupt_stack_push(); }
-                }
-                self.pc = 0x0018;
-            },
-
-            0xE7 => {                                                       // RST 4
-                match direct_rst {
-                    false => self.interrupt_stack_push(),
-                    true => { self.pc +=1; self.interrupt_stack_push(); }
-                }
-                self.pc = 0x0020;
-            },
-
-            0xEF => {                                                       // RST 5
-                match direct_rst {
-                    false => self.interrupt_stack_push(),
-                    true => { self.pc +=1; self.interrupt_stack_push(); }
-                }
-                self.pc = 0x0028;
+            // EI Enable interrupts, after the next instruction
+            0xFB => {
+                self.inte = true;
+                self.ei_delay = true;
+                self.trace(TraceEvent::InterruptEnable(true));
             },
-
-            0xF7 => {                                                       // RST 6
-                match direct_rst {
-                    false => self.interrupt_stack_push(),
-                    true => { self.pc +=1; self.interrupt_stack_push(); }
-                }
-                self.pc = 0x0030;
+            // DI Disable Interrupts
+            0xF3 => {
+                self.inte = false;
+                self.trace(TraceEvent::InterruptEnable(false));
             },
 
-            0xFF => {                                                       // RST 7
-                match direct_rst {
-                    false => self.interrupt_stack_push(),
-                    true => { self.pc +=1; self.interrupt_stack_push(); }
-                }
-                self.pc = 0x0038;
-            },
+            /* RST (Restart) instructions */
+            0xC7 => self.rst(0x0000),                                       // RST 0
+            0xCF => self.rst(0x0008),                                       // RST 1
+            0xD7 => self.rst(0x0010),                                       // RST 2
+            0xDF => self.rst(0x0018),                                       // RST 3
+            0xE7 => self.rst(0x0020),                                       // RST 4
+            0xEF => self.rst(0x0028),                                       // RST 5
+            0xF7 => self.rst(0x0030),                                       // RST 6
+            0xFF => self.rst(0x0038),                                       // RST 7
 
             /* Input / output instructions */
             // IN Input
             0xDB => {
-                // To implement yourself
+                // A keeps its value if no device is attached to the port
+                if let Some(d) = self.io_read(d8) { self.reg.a = d }
             },
 
             // OUT Output
-            0xD3 => {
-                // To implement yourself
-            },
+            0xD3 => self.io_write(d8, self.reg.a),
 
             _ => {}
         }
 
-        if self.debug.switch
-        { self.debug.string = match opcode {
-            0xC7 | 0xCF | 0xD7 | 0xDF | 0xE7 | 0xEF | 0xF7 | 0xFF =>  String::from("RST"),
-            _ => format!("{}\nPC : {:#06x}\tSP : {:#06x}\tS : {}\tZ : {}\tA : {}\tP : {}\tC : {}\nB : {:#04x}\tC : {:#04x}\tD : {:#04x}\tE : {:#04x}\tH : {:#04x}\tL : {:#04x}\tA : {:#04x}\t(SP) : {:#06x}\n", self.dasm(pc), pc, self.sp, self.flags.s as i32, self.flags.z as i32, self.flags.a as i32, self.flags.p as i32, self.flags.c as i32, self.reg.b, self.reg.c, self.reg.d, self.reg.e, self.reg.h, self.reg.l, self.reg.a, self.bus.read_word(self.sp)),
-            }
-        }
-
         match opcode {
             0xe9 | 0xc3 | 0xDA | 0xD2 | 0xCA | 0xC2 | 0xFA | 0xF2 | 0xEA | 0xE2 |
             0xCD | 0xDC | 0xD4 | 0xCC | 0xC4 | 0xFC | 0xF4 | 0xEC | 0xE4 |
             0xC9 | 0xD8 | 0xD0 | 0xC8 | 0xC0 | 0xF8 | 0xF0 | 0xE8 | 0xE0 | 
             0xC7 | 0xCF | 0xD7 | 0xDF | 0xE7 | 0xEF | 0xF7 | 0xFF => {},
-            0x06 | 0x0E | 0x16 | 0x1E | 0x26 | 0x2E | 0x36 | 0x3E |
-            0xC6 | 0xCE | 0xD6 | 0xDE | 0xE6 | 0xEE | 0xF6 | 0xFE |
-            0xDB | 0xD3 => self.pc += 2,
-            0x32 | 0x3A | 0x22 | 0x2A | 0x01 | 0x11 | 0x21 | 0x31 => self.pc += 3,
-            _ => self.pc +=1,
+            _ => self.pc = self.pc.wrapping_add(length(opcode)),
         }
 
-        cycles
+        self.cycles += u64::from(cycles);
+
+        // Idle loop : fast-forward
+        if self.idle.is_some() {
+            let skip = self.idle_loop(pc, opcode);
+            self.cycles += skip;
+            cycles += skip as u32;
+        }
+
+        if self.tracer.is_some() {
+            self.trace(TraceEvent::Instruction { address: pc, bytes, cycles, clock });
+            self.trace(TraceEvent::Registers(self.register_state()));
+        }
 
+        let kind = if interrupt { StepKind::Interrupt } else { StepKind::Instruction };
+        Executed { address: pc, bytes, cycles, kind }
     }
 }
@@ -1,9 +1,42 @@
-use std::{fs::File, io::prelude::*,};
+use std::{cell::RefCell, fs::File, io::prelude::*, ops::RangeInclusive, rc::Rc};
 
 /// The Bus struct is hosting the 8080 memory map and the pending IO operations for outer handling.
 pub struct Bus {
     address_space: Vec<u8>,
     rom_space: Option<ROMSpace>,
+    io_devices: Vec<(RangeInclusive<u8>, Box<dyn IoDevice>)>,
+    // Bytes written since the tracking was enabled
+    written: Option<Vec<bool>>,
+    // Writes recorded while the journal is on
+    journal: Option<Vec<Written>>,
+}
+
+// A write to memory, with what it replaced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Written {
+    pub(crate) address: u16,
+    pub(crate) value: u8,
+    pub(crate) previous: u8,
+    pub(crate) initialized: bool,
+}
+
+/// A device attached to I/O ports, handling the IN and OUT instructions.
+pub trait IoDevice {
+    /// Returns the byte read by IN from the port.
+    fn input(&mut self, port: u8) -> u8;
+    /// Receives the byte written by OUT to the port.
+    fn output(&mut self, port: u8, data: u8);
+}
+
+/// Lets the caller keep a handle on a device attached to the bus.
+impl<T: IoDevice> IoDevice for Rc<RefCell<T>> {
+    fn input(&mut self, port: u8) -> u8 {
+        self.borrow_mut().input(port)
+    }
+
+    fn output(&mut self, port: u8, data: u8) {
+        self.borrow_mut().output(port, data)
+    }
 }
 
 /// Start and end addresses of read-only (ROM) area.
@@ -18,6 +51,26 @@ impl Bus {
         Bus {
             address_space: vec![0; 65536],
             rom_space: None,
+            io_devices: Vec::new(),
+            written: None,
+            journal: None,
+        }
+    }
+
+    /// Attaches a device to a range of I/O ports. When ranges overlap, the first attached device wins.
+    pub fn attach_io(&mut self, ports: RangeInclusive<u8>, device: impl IoDevice + 'static) {
+        self.io_devices.push((ports, Box::new(device)));
+    }
+
+    /// Reads a byte from an I/O port. Returns None if no device is attached to this port.
+    pub fn io_read(&mut self, port: u8) -> Option<u8> {
+        self.io_devices.iter_mut().find(|(p, _)| p.contains(&port)).map(|(_, d)| d.input(port))
+    }
+
+    /// Writes a byte to an I/O port. Ignored if no device is attached to this port.
+    pub fn io_write(&mut self, port: u8, data: u8) {
+        if let Some((_, d)) = self.io_devices.iter_mut().find(|(p, _)| p.contains(&port)) {
+            d.output(port, data);
         }
     }
 
@@ -35,7 +88,9 @@ impl Bus {
     pub fn write_byte(&mut self, address: u16, data: u8) {
         // if rom space is declared, and write operation is requested in rom area : we exit
         if self.rom_space.is_some() && address >= self.rom_space.as_ref().unwrap().start && address <= self.rom_space.as_ref().unwrap().end { return };
+        self.log(address, data);
         self.address_space[usize::from(address)] = data;
+        if let Some(w) = &mut self.written { w[usize::from(address)] = true }
     }
 
     /// Reads a word stored in memory in little endian byte order, returns this word in BE byte order
@@ -52,8 +107,89 @@ impl Bus {
     pub fn write_word(&mut self, address: u16, data: u16) {
         // if rom space is declared, and write operation is requested in rom area : we exit
         if self.rom_space.is_some() && address >= self.rom_space.as_ref().unwrap().start && address <= self.rom_space.as_ref().unwrap().end { return };
+        self.log(address, (data & 0xFF) as u8);
+        self.log(address + 1, (data >> 8) as u8);
         self.address_space[usize::from(address)] = (data & 0xFF) as u8;
         self.address_space[usize::from(address + 1)] = (data >> 8) as u8;
+        if let Some(w) = &mut self.written { w[usize::from(address)..=usize::from(address + 1)].fill(true) }
+    }
+
+    /// Fills the whole address space, except the ROM space, with the bytes returned by f
+    pub fn fill(&mut self, mut f: impl FnMut() -> u8) {
+        for address in 0..=0xffff {
+            if let Some(r) = &self.rom_space {
+                if address >= r.start && address <= r.end { continue }
+            }
+            self.address_space[usize::from(address)] = f();
+        }
+    }
+
+    /// Tracks the bytes written to memory by the CPU, DMA devices, write_byte(), write_word() and load_bin(), to detect
+    /// reads of uninitialized memory. fill() (and power_on()) does not initialize memory. Enabling the tracking forgets the previous writes.
+    pub fn track_writes(&mut self, enable: bool) {
+        self.written = if enable { Some(vec![false; 65536]) } else { None };
+    }
+
+    /// Marks memory as initialized, for instance ROM or memory-mapped hardware.
+    pub fn set_initialized(&mut self, range: RangeInclusive<u16>) {
+        if let Some(w) = &mut self.written { w[usize::from(*range.start())..=usize::from(*range.end())].fill(true) }
+    }
+
+    /// Returns false if writes are tracked and the byte has never been written.
+    pub fn initialized(&self, address: u16) -> bool {
+        self.written.as_ref().is_none_or(|w| w[usize::from(address)])
+    }
+
+    fn log(&mut self, address: u16, value: u8) {
+        if self.journal.is_none() { return }
+        let w = Written { address, value, previous: self.address_space[usize::from(address)], initialized: self.initialized(address) };
+        if let Some(j) = &mut self.journal { j.push(w) }
+    }
+
+    // Records the writes until take_journal() is called
+    pub(crate) fn start_journal(&mut self) {
+        self.journal = Some(Vec::new());
+    }
+
+    pub(crate) fn journaling(&self) -> bool {
+        self.journal.is_some()
+    }
+
+    pub(crate) fn journal_len(&self) -> usize {
+        self.journal.as_ref().map_or(0, |j| j.len())
+    }
+
+    // Writes recorded since the journal had `start` entries
+    pub(crate) fn journal_since(&self, start: usize) -> Vec<Written> {
+        self.journal.as_ref().map_or(Vec::new(), |j| j[start..].to_vec())
+    }
+
+    pub(crate) fn take_journal(&mut self) -> Vec<Written> {
+        self.journal.take().unwrap_or_default()
+    }
+
+    // Undoes a write : the byte is not logged, and gets its previous initialization state back
+    pub(crate) fn restore_byte(&mut self, w: &Written) {
+        self.address_space[usize::from(w.address)] = w.previous;
+        if let Some(m) = &mut self.written { m[usize::from(w.address)] = w.initialized }
+    }
+
+    // Whole address space, ROM included
+    pub(crate) fn memory(&self) -> &[u8] {
+        &self.address_space
+    }
+
+    pub(crate) fn set_memory(&mut self, memory: &[u8]) {
+        self.address_space.copy_from_slice(memory);
+    }
+
+    // Bytes written since the tracking was enabled, None if writes are not tracked
+    pub(crate) fn written(&self) -> Option<&[bool]> {
+        self.written.as_deref()
+    }
+
+    pub(crate) fn set_written(&mut self, written: Option<&[bool]>) {
+        self.written = written.map(|w| w.to_vec());
     }
 
     /// Loads binary data from disk into memory at $0000 + offset
@@ -62,6 +198,7 @@ impl Bus {
         let mut buf = Vec::new();
         f.read_to_end(&mut buf)?;
         self.address_space[org as usize..(buf.len() + org as usize)].clone_from_slice(&buf[..]);
+        if let Some(w) = &mut self.written { w[org as usize..(buf.len() + org as usize)].fill(true) }
         Ok(())
     }
 }
@@ -89,4 +226,45 @@ mod tests {
         b.write_word(0x0000, 0x1be3);
         assert_eq!(b.read_le_word(0x0000), 0xe31b);
     }
+
+    struct Latch(u8);
+
+    impl IoDevice for Latch {
+        fn input(&mut self, _port: u8) -> u8 { self.0 }
+        fn output(&mut self, _port: u8, data: u8) { self.0 = data }
+    }
+
+    #[test]
+    fn io_rw() {
+        let mut b = Bus::new();
+        b.attach_io(0x10..=0x11, Latch(0));
+        b.io_write(0x11, 0x42);
+        assert_eq!(b.io_read(0x10), Some(0x42));
+        assert_eq!(b.io_read(0x12), None);
+    }
+
+    #[test]
+    fn fill() {
+        let mut b = Bus::new();
+        b.write_byte(0x0010, 0x55);
+        b.set_romspace(0x0010, 0x001f);
+        b.fill(|| 0xaa);
+        assert_eq!(b.read_byte(0x000f), 0xaa);
+        assert_eq!(b.read_byte(0x0010), 0x55);
+        assert_eq!(b.read_byte(0xffff), 0xaa);
+    }
+
+    #[test]
+    fn track_writes() {
+        let mut b = Bus::new();
+        assert!(b.initialized(0x1000));
+        b.track_writes(true);
+        b.fill(|| 0xaa);
+        b.write_word(0x1000, 0x1234);
+        b.load_bin("bin/helloworld.bin", 0x0100).unwrap();
+        b.set_initialized(0x2000..=0x2fff);
+        assert!(!b.initialized(0x0000));
+        assert!(b.initialized(0x0100) && b.initialized(0x1001) && b.initialized(0x2fff));
+        assert!(!b.initialized(0x1002));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,112 @@
+//! Reset and power-on.
+//!
+//! RESET mirrors the hardware : pc, the interrupt enable and the halt flip-flops are cleared, the other
+//! registers and memory are left untouched. Power-on sets registers and RAM to chosen contents, to find
+//! software that depends on uninitialized state.
+//! ```rust
+//! use intel8080::{CPU, power::Fill};
+//! let mut c = CPU::new();
+//! c.power_on(Fill::Random(1234));     // same seed, same contents
+//! c.bus.load_bin("bin/helloworld.bin", 0x100).ok();
+//! ```
+use crate::CPU;
+
+/// Power-on contents of registers and RAM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fill {
+    /// Every byte set to this value
+    Pattern(u8),
+    /// Pseudo-random bytes generated from a seed
+    Random(u64),
+}
+
+// xorshift64* pseudo-random generator
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u8 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 56) as u8
+    }
+}
+
+impl CPU {
+    /// RESET : pc is set to $0000, interrupts are disabled and the CPU leaves the halted state.
+    /// Registers, flags, stack pointer and memory are left untouched, and the virtual clock keeps running : cycles_since_reset() starts from 0.
+    pub fn reset(&mut self) {
+        self.pc = 0;
+        self.inte = false;
+        self.ei_delay = false;
+        self.halt = false;
+        self.reset_cycles = self.cycles;
+    }
+
+    /// Power-on : fills registers, flags, stack pointer and RAM (the ROM space is left untouched), then resets the CPU.
+    pub fn power_on(&mut self, fill: Fill) {
+        // The generator state must not be 0
+        let mut rng = Rng(match fill { Fill::Random(seed) => (seed ^ 0x9e37_79b9_7f4a_7c15).max(1), Fill::Pattern(_) => 1 });
+        let mut byte = || match fill {
+            Fill::Pattern(p) => p,
+            Fill::Random(_) => rng.next(),
+        };
+        self.reg.a = byte();
+        self.reg.b = byte();
+        self.reg.c = byte();
+        self.reg.d = byte();
+        self.reg.e = byte();
+        self.reg.h = byte();
+        self.reg.l = byte();
+        self.flags.from_byte(byte());
+        self.sp = u16::from_le_bytes([byte(), byte()]);
+        self.bus.fill(byte);
+        self.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::cpu;
+
+    #[test]
+    fn reset() {
+        let mut c = cpu(&[]);
+        c.pc = 0x1234;
+        c.reg.a = 0x55;
+        c.inte = true;
+        c.halt = true;
+        c.reset();
+        assert_eq!(c.pc, 0);
+        assert_eq!(c.sp, 0xff00);
+        assert_eq!(c.reg.a, 0x55);
+        assert!(!c.inte);
+        assert!(!c.halt);
+    }
+
+    #[test]
+    fn pattern() {
+        let mut c = cpu(&[]);
+        c.pc = 0x1234;
+        c.power_on(Fill::Pattern(0xe5));
+        assert_eq!(c.pc, 0);
+        assert_eq!(c.reg.h, 0xe5);
+        assert_eq!(c.sp, 0xe5e5);
+        assert_eq!(c.bus.read_byte(0x8000), 0xe5);
+    }
+
+    #[test]
+    fn random() {
+        let mut c1 = cpu(&[]);
+        let mut c2 = cpu(&[]);
+        c1.power_on(Fill::Random(42));
+        c2.power_on(Fill::Random(42));
+        assert_eq!(c1.sp, c2.sp);
+        assert!((0..=0xffff).all(|a| c1.bus.read_byte(a) == c2.bus.read_byte(a)));
+        // not a constant pattern
+        assert!((1..=0xffff).any(|a| c1.bus.read_byte(a) != c1.bus.read_byte(0)));
+        c2.power_on(Fill::Random(43));
+        assert!((0..=0xffff).any(|a| c1.bus.read_byte(a) != c2.bus.read_byte(a)));
+    }
+}
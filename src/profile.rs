@@ -0,0 +1,232 @@
+//! Execution profiler : executions and clock cycles per address and per subroutine.
+//!
+//! The Profiler is a trace sink. Subroutines are tracked with the CALL, RST and interrupts which push a
+//! return address, and the stack pointer : a subroutine has returned once the stack pointer is above
+//! its return address. Cycles spent halted are not counted.
+//! ```rust
+//! use intel8080::{CPU, profile::Profiler};
+//! use std::{cell::RefCell, rc::Rc};
+//! let mut c = CPU::new();
+//! c.sp = 0xff00;
+//! c.bus.write_byte(0x0000, 0xcd);     // CALL $0010
+//! c.bus.write_word(0x0001, 0x0010);
+//! c.bus.write_byte(0x0003, 0x76);     // HLT
+//! c.bus.write_byte(0x0010, 0xc9);     // RET
+//! let p = Rc::new(RefCell::new(Profiler::new()));
+//! c.set_tracer(p.clone());
+//! c.run_until(|c| c.halt);
+//! p.borrow_mut().add_symbol(0x0010, "init");
+//! assert_eq!(p.borrow().address(0x0000).cycles, 17);
+//! assert_eq!(p.borrow().folded(), "root 24\nroot;init 10\n");
+//! ```
+use std::collections::HashMap;
+use crate::{disassemble, run::{above, is_call, is_ret}, trace::{TraceEvent, TraceSink}};
+
+/// Executions of an instruction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AddressProfile {
+    pub count: u64,
+    pub cycles: u64,
+}
+
+/// Calls and cycles of a subroutine.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SubroutineProfile {
+    pub calls: u64,
+    /// Cycles spent in the subroutine and in the subroutines it called
+    pub inclusive: u64,
+    /// Cycles spent in the subroutine itself
+    pub exclusive: u64,
+}
+
+#[derive(Default)]
+pub struct Profiler {
+    addresses: HashMap<u16, (AddressProfile, [u8; 3])>,
+    // Key : entry point, None for the code executed outside of any subroutine
+    subroutines: HashMap<Option<u16>, SubroutineProfile>,
+    // Exclusive cycles per call stack
+    stacks: HashMap<Vec<u16>, u64>,
+    // Subroutines being executed : entry points, stack pointers after the return addresses have been pushed
+    path: Vec<u16>,
+    frames: Vec<u16>,
+    symbols: HashMap<u16, String>,
+    // Stack pointer before the current instruction
+    sp: Option<u16>,
+    // Opcode of the current instruction
+    current: Option<u8>,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    /// Names a subroutine in the reports.
+    pub fn add_symbol(&mut self, address: u16, name: &str) {
+        self.symbols.insert(address, name.to_string());
+    }
+
+    /// Clears the statistics. Symbols are kept.
+    pub fn clear(&mut self) {
+        self.addresses.clear();
+        self.subroutines.clear();
+        self.stacks.clear();
+        self.frames.clear();
+        self.path.clear();
+    }
+
+    pub fn address(&self, address: u16) -> AddressProfile {
+        self.addresses.get(&address).map(|a| a.0).unwrap_or_default()
+    }
+
+    /// Returns a subroutine profile. None : code executed outside of any subroutine.
+    pub fn subroutine(&self, entry: Option<u16>) -> SubroutineProfile {
+        self.subroutines.get(&entry).copied().unwrap_or_default()
+    }
+
+    fn name(&self, entry: Option<u16>) -> String {
+        match entry {
+            None => String::from("root"),
+            Some(a) => self.symbols.get(&a).cloned().unwrap_or_else(|| format!("${:04x}", a)),
+        }
+    }
+
+    fn instruction(&mut self, address: u16, bytes: [u8; 3], cycles: u32) {
+        let cycles = u64::from(cycles);
+        let a = self.addresses.entry(address).or_default();
+        a.0.count += 1;
+        a.0.cycles += cycles;
+        a.1 = bytes;
+        // The instruction belongs to the current subroutine, and to its callers
+        self.subroutines.entry(self.path.last().copied()).or_default().exclusive += cycles;
+        self.subroutines.entry(None).or_default().inclusive += cycles;
+        for (n, e) in self.path.iter().enumerate() {
+            // Recursive calls are counted once
+            if !self.path[..n].contains(e) { self.subroutines.entry(Some(*e)).or_default().inclusive += cycles }
+        }
+        match self.stacks.get_mut(self.path.as_slice()) {
+            Some(c) => *c += cycles,
+            None => { self.stacks.insert(self.path.clone(), cycles); },
+        }
+    }
+
+    // Follows the calls and returns once the registers after the instruction are known
+    fn registers(&mut self, pc: u16, sp: u16) {
+        if let (Some(opcode), Some(before)) = (self.current.take(), self.sp) {
+            // Call taken : the return address has been pushed
+            if is_call(opcode) && sp == before.wrapping_sub(2) {
+                self.frames.push(sp);
+                self.path.push(pc);
+                self.subroutines.entry(Some(pc)).or_default().calls += 1;
+            // Return taken : the frame of the popped address, and the frames left below it, are closed
+            } else if is_ret(opcode) && sp == before.wrapping_add(2) {
+                while self.frames.last().is_some_and(|f| !above(*f, before)) {
+                    self.frames.pop();
+                    self.path.pop();
+                }
+            }
+        }
+        // Stack pointer moved above the return address
+        while self.frames.last().is_some_and(|f| above(sp, *f)) {
+            self.frames.pop();
+            self.path.pop();
+        }
+        self.sp = Some(sp);
+    }
+
+    /// Text report : subroutines sorted by inclusive cycles, then addresses sorted by cycles.
+    pub fn report(&self) -> String {
+        let total = self.subroutine(None).inclusive.max(1) as f64;
+        let mut s = format!("{:>10} {:>14} {:>7} {:>14} {:>7}  subroutine\n", "calls", "inclusive", "%", "self", "%");
+        let mut subroutines: Vec<_> = self.subroutines.iter().collect();
+        subroutines.sort_by(|a, b| b.1.inclusive.cmp(&a.1.inclusive).then(a.0.cmp(b.0)));
+        for (entry, p) in subroutines {
+            s += &format!("{:>10} {:>14} {:>6.2}% {:>14} {:>6.2}%  {}\n", p.calls, p.inclusive, p.inclusive as f64 * 100.0 / total,
+                p.exclusive, p.exclusive as f64 * 100.0 / total, self.name(*entry));
+        }
+        s += &format!("\n{:>10} {:>14} {:>14} {:>7}  instruction\n", "address", "count", "cycles", "%");
+        let mut addresses: Vec<_> = self.addresses.iter().collect();
+        addresses.sort_by(|a, b| b.1.0.cycles.cmp(&a.1.0.cycles).then(a.0.cmp(b.0)));
+        for (address, (p, bytes)) in addresses {
+            s += &format!("{:>10} {:>14} {:>14} {:>6.2}%  {}\n", format!("${:04x}", address), p.count, p.cycles,
+                p.cycles as f64 * 100.0 / total, disassemble(*bytes));
+        }
+        s
+    }
+
+    /// Folded stacks ("root;caller;callee cycles" lines), the input format of flame graph tools.
+    pub fn folded(&self) -> String {
+        let mut lines: Vec<String> = self.stacks.iter().map(|(stack, cycles)| {
+            let mut names = vec![self.name(None)];
+            names.extend(stack.iter().map(|e| self.name(Some(*e))));
+            format!("{} {}", names.join(";"), cycles)
+        }).collect();
+        lines.sort();
+        lines.iter().map(|l| l.clone() + "\n").collect()
+    }
+}
+
+impl TraceSink for Profiler {
+    fn event(&mut self, event: &TraceEvent) {
+        match *event {
+            TraceEvent::Instruction { address, bytes, cycles, .. } => {
+                self.instruction(address, bytes, cycles);
+                self.current = Some(bytes[0]);
+            },
+            TraceEvent::Registers(r) => self.registers(r.pc, r.sp),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{attach, cpu};
+
+    // $0000 : CALL $0010 / CALL $0010 / HLT
+    // $0010 : CALL $0020 / RET
+    // $0020 : RET
+    const PROGRAM: &[(u16, &[u8])] = &[(0x0000, &[0xcd, 0x10, 0x00, 0xcd, 0x10, 0x00, 0x76]), (0x0010, &[0xcd, 0x20, 0x00, 0xc9]), (0x0020, &[0xc9])];
+
+    #[test]
+    fn subroutines() {
+        let mut c = cpu(PROGRAM);
+        let p = attach(&mut c, Profiler::new());
+        c.run_until(|c| c.halt);
+        let p = p.borrow();
+        assert_eq!(p.address(0x0010), AddressProfile { count: 2, cycles: 34 });
+        assert_eq!(p.subroutine(Some(0x0010)), SubroutineProfile { calls: 2, inclusive: 2 * (17 + 10 + 10), exclusive: 2 * (17 + 10) });
+        assert_eq!(p.subroutine(Some(0x0020)), SubroutineProfile { calls: 2, inclusive: 20, exclusive: 20 });
+        assert_eq!(p.subroutine(None), SubroutineProfile { calls: 0, inclusive: 17 * 2 + 7 + 74, exclusive: 17 * 2 + 7 });
+        assert_eq!(p.folded(), "root 41\nroot;$0010 54\nroot;$0010;$0020 20\n");
+        let report = p.report();
+        assert_eq!(report.lines().nth(1).unwrap(), "         0            115 100.00%             41  35.65%  root");
+        assert!(report.contains("     $0000              1             17"));
+    }
+
+    #[test]
+    fn interrupt_and_stack_manipulation() {
+        // $0000 : JMP $0000, $0038 : POP H / JMP $0000 (returns without RET)
+        let mut c = cpu(&[(0x0000, &[0xc3]), (0x0038, &[0xe1, 0xc3])]);
+        let p = attach(&mut c, Profiler::new());
+        c.inte = true;
+        c.int = (true, 0xff);                           // RST 7
+        c.run_cycles(100);
+        let p = p.borrow();
+        assert_eq!(p.subroutine(Some(0x0038)), SubroutineProfile { calls: 1, inclusive: 10, exclusive: 10 });
+        assert!(p.frames.is_empty());
+    }
+
+    #[test]
+    fn wrapped_stack() {
+        // $0000 : CALL $0010 / JMP $0000, $0010 : RET (the return address is at $fffe)
+        let mut c = cpu(&[(0x0000, &[0xcd, 0x10, 0x00, 0xc3]), (0x0010, &[0xc9])]);
+        c.sp = 0x0000;
+        let p = attach(&mut c, Profiler::new());
+        c.run_cycles(370);
+        let p = p.borrow();
+        assert!(p.frames.is_empty());
+        assert_eq!(p.folded(), "root 270\nroot;$0010 100\n");
+    }
+}
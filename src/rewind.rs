@@ -0,0 +1,274 @@
+//! Reverse execution : instructions executed through Rewind can be undone.
+//!
+//! Each instruction is recorded in an undo log : registers before the instruction, and the previous value of
+//! every byte written during its execution (by the instruction, DMA devices or scheduled events). Snapshots
+//! of the whole machine are also taken periodically : when the undo log does not go back far enough, the CPU
+//! is restored from a snapshot and the instructions are executed again.
+//!
+//! Only the CPU and memory are rewound : I/O devices, interrupt controllers, scheduled events and memory
+//! written by the host between instructions keep their current state.
+//! ```rust
+//! use intel8080::{CPU, rewind::Rewind};
+//! let mut c = CPU::new();
+//! c.reg.set_hl(0x1000);
+//! c.bus.write_byte(0x0000, 0x34);     // INR M
+//! c.bus.write_byte(0x0001, 0xc3);     // JMP $0000
+//! c.bus.write_word(0x0002, 0x0000);
+//! let mut r = Rewind::new(1000);
+//! for _ in 0..10 { r.execute(&mut c); }
+//! assert_eq!(c.bus.read_byte(0x1000), 5);
+//! assert!(r.step_back(&mut c));
+//! assert_eq!(c.pc, 0x0001);
+//! assert_eq!(r.back_to_write(&mut c, 0x1000), Some(0x0000));
+//! assert_eq!(c.bus.read_byte(0x1000), 4);
+//! assert!(r.rewind_to(&mut c, 15));
+//! assert_eq!((c.cycles(), c.bus.read_byte(0x1000)), (10, 1));
+//! ```
+use std::collections::VecDeque;
+use crate::{memory::Written, CPU};
+
+// Registers and virtual clock
+#[derive(Clone, Copy)]
+struct State {
+    regs: [u8; 8],
+    pc: u16,
+    sp: u16,
+    inte: bool,
+    ei_delay: bool,
+    halt: bool,
+    int: (bool, u8),
+    cycles: u64,
+}
+
+impl State {
+    fn save(c: &CPU) -> State {
+        let r = &c.reg;
+        State {
+            regs: [r.a, r.b, r.c, r.d, r.e, r.h, r.l, c.flags.as_byte()],
+            pc: c.pc, sp: c.sp, inte: c.inte, ei_delay: c.ei_delay, halt: c.halt, int: c.int, cycles: c.cycles,
+        }
+    }
+
+    fn restore(&self, c: &mut CPU) {
+        let [a, b, cc, d, e, h, l, f] = self.regs;
+        (c.reg.a, c.reg.b, c.reg.c, c.reg.d, c.reg.e, c.reg.h, c.reg.l) = (a, b, cc, d, e, h, l);
+        c.flags.from_byte(f);
+        (c.pc, c.sp, c.inte, c.ei_delay, c.halt, c.int, c.cycles) = (self.pc, self.sp, self.inte, self.ei_delay, self.halt, self.int, self.cycles);
+    }
+}
+
+/// Registers, virtual clock, memory and initialized bytes.
+#[derive(Clone)]
+pub struct Snapshot {
+    state: State,
+    memory: Vec<u8>,
+    // Initialized bytes, when the bus tracks writes
+    written: Option<Vec<bool>>,
+}
+
+impl Snapshot {
+    /// Virtual clock when the snapshot was taken.
+    pub fn cycles(&self) -> u64 {
+        self.state.cycles
+    }
+}
+
+impl CPU {
+    /// Saves the registers, the virtual clock, the memory and the initialized bytes (Bus::track_writes()).
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot { state: State::save(self), memory: self.bus.memory().to_vec(), written: self.bus.written().map(|w| w.to_vec()) }
+    }
+
+    /// Restores a snapshot.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        snapshot.state.restore(self);
+        self.bus.set_memory(&snapshot.memory);
+        self.bus.set_written(snapshot.written.as_deref());
+    }
+}
+
+// An executed instruction : state before it, bytes it wrote
+struct Entry {
+    state: State,
+    writes: Vec<Written>,
+}
+
+pub struct Rewind {
+    log: VecDeque<Entry>,
+    capacity: usize,
+    snapshots: VecDeque<Snapshot>,
+    interval: u64,
+    max_snapshots: usize,
+}
+
+impl Rewind {
+    /// Keeps the last `capacity` instructions in the undo log, and a snapshot every million cycles (the last 16).
+    pub fn new(capacity: usize) -> Rewind {
+        Rewind { log: VecDeque::new(), capacity, snapshots: VecDeque::new(), interval: 1_000_000, max_snapshots: 16 }
+    }
+
+    /// Takes a snapshot every `interval` cycles and keeps the last `count` ones. A count of 0 disables the snapshots.
+    pub fn set_snapshots(&mut self, interval: u64, count: usize) {
+        self.interval = interval.max(1);
+        self.max_snapshots = count;
+        while self.snapshots.len() > count { self.snapshots.pop_front(); }
+    }
+
+    /// Number of instructions that can be undone.
+    pub fn len(&self) -> usize {
+        self.log.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.log.is_empty()
+    }
+
+    /// Forgets the history.
+    pub fn clear(&mut self) {
+        self.log.clear();
+        self.snapshots.clear();
+    }
+
+    /// Executes one instruction, like CPU::execute(), and records it.
+    pub fn execute(&mut self, c: &mut CPU) -> u32 {
+        if self.max_snapshots > 0 && self.snapshots.back().is_none_or(|s| c.cycles >= s.cycles() + self.interval) {
+            if self.snapshots.len() == self.max_snapshots { self.snapshots.pop_front(); }
+            self.snapshots.push_back(c.snapshot());
+        }
+        let state = State::save(c);
+        c.bus.start_journal();
+        let cycles = c.exec().cycles;
+        let writes = c.bus.take_journal();
+        if self.log.len() == self.capacity { self.log.pop_front(); }
+        if self.capacity > 0 { self.log.push_back(Entry { state, writes }) }
+        cycles
+    }
+
+    // Undoes the last instruction of the log
+    fn undo(&mut self, c: &mut CPU) -> Option<Entry> {
+        let e = self.log.pop_back()?;
+        for w in e.writes.iter().rev() { c.bus.restore_byte(w) }
+        e.state.restore(c);
+        // Snapshots taken after this point belong to a future which may not happen again
+        while self.snapshots.back().is_some_and(|s| s.cycles() > c.cycles) { self.snapshots.pop_back(); }
+        Some(e)
+    }
+
+    /// Undoes the last instruction. Returns false if the undo log is empty.
+    pub fn step_back(&mut self, c: &mut CPU) -> bool {
+        self.undo(c).is_some()
+    }
+
+    /// Runs backwards to the last instruction which wrote to the address, and returns the address of this instruction.
+    /// The CPU is left just before its execution. Returns None, without undoing anything, if no instruction of the log wrote to the address.
+    pub fn back_to_write(&mut self, c: &mut CPU, address: u16) -> Option<u16> {
+        if !self.log.iter().any(|e| e.writes.iter().any(|w| w.address == address)) { return None }
+        while let Some(e) = self.undo(c) {
+            if e.writes.iter().any(|w| w.address == address) { break }
+        }
+        Some(c.pc)
+    }
+
+    /// Rewinds to the last instruction boundary at or before a cycle count. If the undo log does not go back far enough,
+    /// the CPU is restored from a snapshot and executes forward. Returns false if the history does not go back far enough.
+    ///
+    /// The replay really executes the instructions again : IN and OUT reach the I/O devices in their current state, and
+    /// only the events still scheduled fire. A program depending on devices or events may then follow another path.
+    pub fn rewind_to(&mut self, c: &mut CPU, cycles: u64) -> bool {
+        while c.cycles > cycles && self.undo(c).is_some() {}
+        if c.cycles <= cycles { return true }
+        let snapshot = match self.snapshots.iter().rev().find(|s| s.cycles() <= cycles) {
+            Some(s) => s.clone(),
+            None => return false,
+        };
+        c.restore(&snapshot);
+        self.log.clear();
+        while self.snapshots.back().is_some_and(|s| s.cycles() > c.cycles) { self.snapshots.pop_back(); }
+        while c.cycles < cycles { self.execute(c); }
+        if c.cycles > cycles { self.undo(c); }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::cpu;
+
+    // $0000 : LXI SP,$ff00 / CALL $0010 / HLT
+    // $0010 : PUSH PSW / MVI M,$55 / XTHL / INX H / SHLD $2000 / POP PSW / RET
+    fn program() -> CPU {
+        let mut c = cpu(&[(0x0000, &[0x31, 0x00, 0xff, 0xcd, 0x10, 0x00, 0x76]),
+            (0x0010, &[0xf5, 0x36, 0x55, 0xe3, 0x23, 0x22, 0x00, 0x20, 0xf1, 0xc9])]);
+        c.reg.set_hl(0x1000);
+        c.reg.a = 0x12;
+        c
+    }
+
+    #[test]
+    fn undo_all() {
+        let mut c = program();
+        let before = c.snapshot();
+        let mut r = Rewind::new(100);
+        while !c.halt { r.execute(&mut c); }
+        assert_ne!(c.bus.read_word(0x2000), 0);
+        while r.step_back(&mut c) {}
+        assert_eq!(c.pc, 0x0000);
+        assert_eq!(c.cycles(), 0);
+        assert!(c.bus.memory() == before.memory.as_slice());
+        assert_eq!((c.reg.a, c.reg.get_hl(), c.sp), (0x12, 0x1000, 0xff00));
+    }
+
+    #[test]
+    fn replay() {
+        let mut c = program();
+        let mut r = Rewind::new(2);
+        r.set_snapshots(20, 4);
+        while !c.halt { r.execute(&mut c); }
+        let end = c.cycles();
+        // the undo log only holds 2 instructions : restored from the snapshot taken at cycle 27, then replayed
+        assert!(r.rewind_to(&mut c, 40));
+        assert_eq!((c.cycles(), c.pc), (10 + 17 + 11, 0x0011));
+        assert_eq!(c.bus.read_byte(0x1000), 0);
+        while !c.halt { r.execute(&mut c); }
+        assert_eq!(c.cycles(), end);
+        assert!(!r.rewind_to(&mut c, 5));
+    }
+
+    #[test]
+    fn dma_and_initialization() {
+        struct Dma(bool);
+        impl crate::dma::DmaDevice for Dma {
+            fn hold(&self) -> bool { self.0 }
+            fn transfer(&mut self, bus: &mut crate::memory::Bus) -> u32 { bus.write_byte(0x3000, 0xaa); self.0 = false; 3 }
+        }
+        let mut c = program();
+        c.bus.track_writes(true);
+        c.attach_dma(Dma(true));
+        let mut r = Rewind::new(10);
+        r.execute(&mut c);
+        r.execute(&mut c);
+        assert_eq!(c.bus.read_byte(0x3000), 0xaa);
+        // undone without being marked as initialized
+        while r.step_back(&mut c) {}
+        assert_eq!(c.bus.read_byte(0x3000), 0);
+        assert!(!c.bus.initialized(0x3000) && !c.bus.initialized(0xfefe));
+    }
+
+    #[test]
+    fn snapshot() {
+        let mut c = program();
+        let s = c.snapshot();
+        c.run_until(|c| c.halt);
+        c.restore(&s);
+        assert_eq!((c.pc, c.cycles(), c.halt, c.bus.read_byte(0x1000)), (0, 0, false, 0));
+        // initialized bytes
+        c.bus.track_writes(true);
+        c.bus.set_initialized(0x0000..=0x001f);
+        let s = c.snapshot();
+        c.run_until(|c| c.halt);
+        assert!(c.bus.initialized(0x1000));
+        c.restore(&s);
+        assert!(c.bus.initialized(0x0010) && !c.bus.initialized(0x1000));
+    }
+}
@@ -0,0 +1,174 @@
+//! Run loops built on `CPU::execute`.
+//! ```rust
+//! use intel8080::{CPU, run::StopReason};
+//! let mut c = CPU::new();
+//! c.bus.write_byte(0x0000, 0x3c);     // INR A
+//! c.bus.write_byte(0x0001, 0xc3);     // JMP $0000
+//! c.bus.write_word(0x0002, 0x0000);
+//! let r = c.run_until(|c| c.reg.a == 3);
+//! assert_eq!(r.reason, StopReason::Condition);
+//! assert_eq!(r.cycles, 3 * 5 + 2 * 10);
+//! ```
+use crate::CPU;
+
+/// Why a run loop returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// The cycle budget has been consumed.
+    Cycles,
+    /// The stop condition has been met.
+    Condition,
+    /// The instruction (or the called subroutine) has been stepped over.
+    Step,
+    /// The current subroutine has returned.
+    Returned,
+    /// The CPU is halted with interrupts disabled : nothing can wake it up.
+    Halted,
+}
+
+/// Stop reason and number of clock cycles consumed by a run loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunResult {
+    pub reason: StopReason,
+    pub cycles: u64,
+}
+
+// CALL, conditional CALL and RST opcodes
+pub(crate) fn is_call(opcode: u8) -> bool {
+    matches!(opcode, 0xCD | 0xDC | 0xD4 | 0xCC | 0xC4 | 0xFC | 0xF4 | 0xEC | 0xE4) || opcode & 0xC7 == 0xC7
+}
+
+// RET and conditional RET opcodes
+pub(crate) fn is_ret(opcode: u8) -> bool {
+    opcode == 0xC9 || opcode & 0xC7 == 0xC0
+}
+
+// Stack pointer above `frame` : the stack may wrap around $0000 (LXI SP,$0000)
+pub(crate) fn above(sp: u16, frame: u16) -> bool {
+    (sp.wrapping_sub(frame) as i16) > 0
+}
+
+impl CPU {
+    // The CPU can't make progress any more
+    fn dead(&self) -> bool {
+        self.halt && !self.inte
+    }
+
+    /// Executes instructions until at least n clock cycles have been consumed.
+    /// A halted CPU keeps consuming cycles until the budget is reached.
+    pub fn run_cycles(&mut self, n: u64) -> RunResult {
+        let mut cycles = 0;
+        let limit = self.set_idle_limit(self.cycles() + n);
+        while cycles < n {
+            cycles += u64::from(self.execute());
+        }
+        self.set_idle_limit(limit);
+        RunResult { reason: StopReason::Cycles, cycles }
+    }
+
+    /// Executes instructions until the condition, checked after each instruction, is true.
+    pub fn run_until(&mut self, mut condition: impl FnMut(&CPU) -> bool) -> RunResult {
+        let mut cycles = 0;
+        loop {
+            cycles += u64::from(self.execute());
+            if condition(self) { return RunResult { reason: StopReason::Condition, cycles } }
+            if self.dead() { return RunResult { reason: StopReason::Halted, cycles } }
+        }
+    }
+
+    /// Executes one instruction. A CALL or RST is executed as a single step : execution goes on
+    /// until the subroutine returns to the instruction following the call.
+    pub fn step_over(&mut self) -> RunResult {
+        let (pc, sp) = (self.pc, self.sp);
+        let opcode = self.bus.read_byte(pc);
+        let next = pc.wrapping_add(crate::length(opcode));
+        let mut cycles = u64::from(self.execute());
+        // Call taken : the return address has been pushed
+        if is_call(opcode) && self.sp == sp.wrapping_sub(2) && self.pc != next && self.bus.read_word(self.sp) == next {
+            let r = self.run_until(|c| c.pc == next && c.sp == sp);
+            cycles += r.cycles;
+            if r.reason == StopReason::Halted { return RunResult { reason: StopReason::Halted, cycles } }
+        }
+        RunResult { reason: StopReason::Step, cycles }
+    }
+
+    /// Executes instructions until the current subroutine returns to its caller.
+    pub fn step_out(&mut self) -> RunResult {
+        let sp = self.sp;
+        let mut cycles = 0;
+        loop {
+            let (opcode, before) = (self.bus.read_byte(self.pc), self.sp);
+            cycles += u64::from(self.execute());
+            // Return taken, and the return address popped was above the stack pointer at the start
+            if is_ret(opcode) && self.sp == before.wrapping_add(2) && above(self.sp, sp) { return RunResult { reason: StopReason::Returned, cycles } }
+            if self.dead() { return RunResult { reason: StopReason::Halted, cycles } }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::cpu;
+
+    // $0000 : CALL $0010 / INR A / HLT
+    // $0010 : INR B / CALL $0020 / RET
+    // $0020 : INR C / RET
+    const PROGRAM: &[(u16, &[u8])] = &[(0x0000, &[0xcd, 0x10, 0x00, 0x3c, 0x76]), (0x0010, &[0x04, 0xcd, 0x20, 0x00, 0xc9]), (0x0020, &[0x0c, 0xc9])];
+
+    #[test]
+    fn run_cycles() {
+        let mut c = cpu(PROGRAM);
+        let r = c.run_cycles(20);
+        assert_eq!(r, RunResult { reason: StopReason::Cycles, cycles: 22 });
+        assert_eq!(c.pc, 0x0011);
+        // halted : cycles go on
+        c.run_cycles(1000);
+        assert!(c.halt);
+        assert_eq!(c.run_cycles(8).cycles, 8);
+    }
+
+    #[test]
+    fn step_over() {
+        let mut c = cpu(PROGRAM);
+        let r = c.step_over();
+        assert_eq!(r, RunResult { reason: StopReason::Step, cycles: 17 + 5 + 17 + 5 + 10 + 10 });
+        assert_eq!(c.pc, 0x0003);
+        assert_eq!((c.reg.b, c.reg.c), (1, 1));
+        assert_eq!(c.step_over(), RunResult { reason: StopReason::Step, cycles: 5 });
+        assert_eq!(c.pc, 0x0004);
+    }
+
+    #[test]
+    fn step_out() {
+        let mut c = cpu(PROGRAM);
+        c.execute();
+        c.execute();
+        c.execute();
+        assert_eq!(c.pc, 0x0020);
+        // returns to $0014, not to $0003
+        assert_eq!(c.step_out(), RunResult { reason: StopReason::Returned, cycles: 15 });
+        assert_eq!(c.pc, 0x0014);
+        c.step_out();
+        assert_eq!(c.pc, 0x0003);
+    }
+
+    #[test]
+    fn step_out_wrapped_stack() {
+        let mut c = cpu(PROGRAM);
+        c.sp = 0x0000;
+        c.execute();
+        c.execute();
+        assert_eq!((c.pc, c.sp), (0x0011, 0xfffe));
+        assert_eq!(c.step_out().reason, StopReason::Returned);
+        assert_eq!((c.pc, c.sp), (0x0003, 0x0000));
+    }
+
+    #[test]
+    fn halted() {
+        let mut c = cpu(PROGRAM);
+        let r = c.run_until(|c| c.reg.a == 2);
+        assert_eq!(r.reason, StopReason::Halted);
+        assert_eq!(c.pc, 0x0005);
+    }
+}
@@ -0,0 +1,143 @@
+//! Device events scheduled on the CPU virtual clock.
+//!
+//! Events are fired by `CPU::execute`, between two instructions, as soon as the cycle count reaches
+//! their timestamp. A callback can raise an interrupt : it is seen by the very next instruction.
+//! ```rust
+//! use intel8080::CPU;
+//! let mut c = CPU::new();
+//! c.bus.write_byte(0x0000, 0xc3);     // JMP $0000
+//! // 60 Hz timer at 2 MHz : RST 7 every 33333 cycles
+//! c.schedule(33333, |c| { c.int = (true, 0xff); Some(33333) });
+//! c.run_cycles(100000);
+//! ```
+use crate::CPU;
+
+/// Identifies a scheduled event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EventId(u64);
+
+/// Called with the CPU when the event is due. Returns the delay (cycles) before the next call for a periodic event.
+pub type Callback = Box<dyn FnMut(&mut CPU) -> Option<u64>>;
+
+struct Event {
+    at: u64,
+    id: EventId,
+    callback: Callback,
+}
+
+/// Events sorted by timestamp (virtual clock, see CPU::cycles()).
+#[derive(Default)]
+pub struct Scheduler {
+    events: Vec<Event>,
+    next_id: u64,
+}
+
+impl Scheduler {
+    pub fn new() -> Scheduler {
+        Scheduler::default()
+    }
+
+    /// Schedules a callback at an absolute cycle count.
+    pub fn schedule_at(&mut self, at: u64, callback: impl FnMut(&mut CPU) -> Option<u64> + 'static) -> EventId {
+        let id = EventId(self.next_id);
+        self.next_id += 1;
+        self.insert(Event { at, id, callback: Box::new(callback) });
+        id
+    }
+
+    // Events with the same timestamp are fired in scheduling order
+    fn insert(&mut self, event: Event) {
+        let i = self.events.partition_point(|e| (e.at, e.id.0) < (event.at, event.id.0));
+        self.events.insert(i, event);
+    }
+
+    /// Cancels an event. Returns false if the event has already been fired or cancelled.
+    pub fn cancel(&mut self, id: EventId) -> bool {
+        match self.events.iter().position(|e| e.id == id) {
+            Some(i) => { self.events.remove(i); true },
+            None => false,
+        }
+    }
+
+    /// Returns the timestamp of the next event.
+    pub fn next_event(&self) -> Option<u64> {
+        self.events.first().map(|e| e.at)
+    }
+
+    /// Returns the number of pending events.
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Removes all the events.
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+impl CPU {
+    /// Schedules a callback in `delay` clock cycles from now. The callback returns the delay before
+    /// its next call for a periodic event, or None for a one-shot event.
+    pub fn schedule(&mut self, delay: u64, callback: impl FnMut(&mut CPU) -> Option<u64> + 'static) -> EventId {
+        let at = self.cycles() + delay;
+        self.scheduler.schedule_at(at, callback)
+    }
+
+    // Fires the events due at the current cycle count
+    pub(crate) fn fire_events(&mut self) {
+        while self.scheduler.next_event().is_some_and(|at| at <= self.cycles()) {
+            let mut e = self.scheduler.events.remove(0);
+            // Periodic events are rescheduled from their timestamp, not from the firing time : they don't drift
+            if let Some(delay) = (e.callback)(self) {
+                e.at += delay.max(1);
+                self.scheduler.insert(e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testutil::cpu;
+    use std::{cell::RefCell, rc::Rc};
+
+    #[test]
+    fn one_shot() {
+        let fired = Rc::new(RefCell::new(Vec::new()));
+        let mut c = cpu(&[]);
+        let f = fired.clone();
+        c.schedule(12, move |c| { f.borrow_mut().push(c.cycles()); None });
+        c.run_cycles(100);
+        // NOPs : fired between the 3rd and 4th instruction
+        assert_eq!(*fired.borrow(), vec![12]);
+        assert!(c.scheduler.is_empty());
+    }
+
+    #[test]
+    fn periodic_interrupt() {
+        // $0000 : EI / JMP $0001, $0038 : INR A / EI / RET
+        let mut c = cpu(&[(0x0000, &[0xfb, 0xc3, 0x01, 0x00]), (0x0038, &[0x3c, 0xfb, 0xc9])]);
+        c.schedule(100, |c| { c.int = (true, 0xff); Some(100) });
+        c.run_cycles(1050);
+        assert_eq!(c.reg.a, 10);
+        assert_eq!(c.scheduler.next_event(), Some(1100));
+    }
+
+    #[test]
+    fn order_and_cancel() {
+        let fired = Rc::new(RefCell::new(Vec::new()));
+        let mut c = cpu(&[]);
+        let (f1, f2, f3) = (fired.clone(), fired.clone(), fired.clone());
+        c.schedule(8, move |_| { f1.borrow_mut().push(1); None });
+        let id = c.schedule(4, move |_| { f2.borrow_mut().push(2); None });
+        c.schedule(8, move |_| { f3.borrow_mut().push(3); None });
+        assert!(c.scheduler.cancel(id));
+        assert!(!c.scheduler.cancel(id));
+        c.run_cycles(20);
+        assert_eq!(*fired.borrow(), vec![1, 3]);
+    }
+}
@@ -0,0 +1,155 @@
+//! Self-modifying code detection.
+//!
+//! SmcDetector is a trace sink. It records the bytes fetched by the CPU, and reports the instructions or the
+//! DMA devices which write into them. A handler can be called on each of these writes, for instance to
+//! invalidate a decoded instruction cache. Memory written by the host is not checked.
+//! ```rust
+//! use intel8080::{CPU, smc::{SmcDetector, SelfModification}};
+//! use std::{cell::RefCell, rc::Rc};
+//! let mut c = CPU::new();
+//! c.bus.write_byte(0x0000, 0x3c);     // INR A
+//! c.bus.write_byte(0x0001, 0x32);     // STA $0000
+//! c.bus.write_word(0x0002, 0x0000);
+//! c.bus.write_byte(0x0004, 0xc3);     // JMP $0000
+//! let smc = Rc::new(RefCell::new(SmcDetector::new()));
+//! c.set_tracer(smc.clone());
+//! for _ in 0..3 { c.execute(); }
+//! assert_eq!(smc.borrow().modifications(), &[SelfModification { address: Some(0x0001), target: 0x0000, value: 0x01 }]);
+//! ```
+use std::collections::HashSet;
+use crate::{step::AccessKind, trace::{TraceEvent, TraceSink}};
+
+/// A write into a byte previously executed as code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelfModification {
+    /// Address of the writing instruction, None for a DMA device
+    pub address: Option<u16>,
+    /// Address of the modified byte
+    pub target: u16,
+    /// Value written
+    pub value: u8,
+}
+
+/// Called on every write into code.
+pub type Handler = Box<dyn FnMut(&SelfModification)>;
+
+pub struct SmcDetector {
+    // Bytes fetched as opcodes or operands
+    code: Vec<bool>,
+    modifications: Vec<SelfModification>,
+    reported: HashSet<(Option<u16>, u16)>,
+    handler: Option<Handler>,
+    // Writes into code of the current instruction, which come before its Instruction event
+    pending: Vec<(u16, u8)>,
+}
+
+impl Default for SmcDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SmcDetector {
+    pub fn new() -> SmcDetector {
+        SmcDetector { code: vec![false; 0x10000], modifications: Vec::new(), reported: HashSet::new(), handler: None, pending: Vec::new() }
+    }
+
+    /// Calls the handler on every write into code.
+    pub fn set_handler(&mut self, handler: impl FnMut(&SelfModification) + 'static) {
+        self.handler = Some(Box::new(handler));
+    }
+
+    /// Returns true if the byte has been fetched as part of an instruction.
+    pub fn executed(&self, address: u16) -> bool {
+        self.code[usize::from(address)]
+    }
+
+    /// Writes into code, each writing instruction and modified byte pair reported once.
+    pub fn modifications(&self) -> &[SelfModification] {
+        &self.modifications
+    }
+
+    fn modified(&mut self, m: SelfModification) {
+        if let Some(h) = self.handler.as_mut() { h(&m) }
+        if self.reported.insert((m.address, m.target)) { self.modifications.push(m) }
+    }
+
+    /// Forgets the executed bytes and the modifications.
+    pub fn clear(&mut self) {
+        self.code.iter_mut().for_each(|c| *c = false);
+        self.modifications.clear();
+        self.reported.clear();
+    }
+}
+
+impl TraceSink for SmcDetector {
+    fn event(&mut self, event: &TraceEvent) {
+        match *event {
+            TraceEvent::Bus(a) => match a.kind {
+                AccessKind::Fetch => self.code[usize::from(a.address)] = true,
+                AccessKind::Write | AccessKind::StackWrite if self.code[usize::from(a.address)] => self.pending.push((a.address, a.value)),
+                AccessKind::DmaWrite if self.code[usize::from(a.address)] => self.modified(SelfModification { address: None, target: a.address, value: a.value }),
+                _ => {}
+            },
+            TraceEvent::Instruction { address, .. } => {
+                for (target, value) in std::mem::take(&mut self.pending) {
+                    self.modified(SelfModification { address: Some(address), target, value });
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{attach, cpu};
+    use std::{cell::RefCell, rc::Rc};
+
+    #[test]
+    fn operand_and_stack() {
+        // $0000 : LXI SP,$000a / LXI H,$0006 / INR M (itself) / INX H / MVI M,$05 (patches INX H) / PUSH B (over MVI)
+        let mut c = cpu(&[(0x0000, &[0x31, 0x0a, 0x00, 0x21, 0x06, 0x00, 0x34, 0x23, 0x36, 0x05, 0xc5])]);
+        let smc = attach(&mut c, SmcDetector::new());
+        let writes = Rc::new(RefCell::new(0));
+        let w = writes.clone();
+        smc.borrow_mut().set_handler(move |_| *w.borrow_mut() += 1);
+        for _ in 0..6 { c.execute(); }
+        assert_eq!(smc.borrow().modifications(), &[
+            SelfModification { address: Some(0x0006), target: 0x0006, value: 0x35 },
+            SelfModification { address: Some(0x0008), target: 0x0007, value: 0x05 },
+            SelfModification { address: Some(0x000a), target: 0x0009, value: 0x00 },
+            SelfModification { address: Some(0x000a), target: 0x0008, value: 0x00 },
+        ]);
+        assert_eq!(*writes.borrow(), 4);
+        assert!(smc.borrow().executed(0x0009) && !smc.borrow().executed(0x000b));
+    }
+
+    #[test]
+    fn dma() {
+        // Overwrites $0000 once
+        struct Loader(bool);
+        impl crate::dma::DmaDevice for Loader {
+            fn hold(&self) -> bool { self.0 }
+            fn transfer(&mut self, bus: &mut crate::memory::Bus) -> u32 { bus.write_byte(0x0000, 0x3c); self.0 = false; 3 }
+        }
+        let loader = Rc::new(RefCell::new(Loader(false)));
+        let mut c = cpu(&[]);
+        c.attach_dma(loader.clone());
+        let smc = attach(&mut c, SmcDetector::new());
+        c.execute();
+        loader.borrow_mut().0 = true;
+        c.execute();
+        assert_eq!(smc.borrow().modifications(), &[SelfModification { address: None, target: 0x0000, value: 0x3c }]);
+    }
+
+    #[test]
+    fn data() {
+        // $0000 : STA $1000 / JMP $0000
+        let mut c = cpu(&[(0x0000, &[0x32, 0x00, 0x10, 0xc3, 0x00, 0x00])]);
+        let smc = attach(&mut c, SmcDetector::new());
+        c.run_cycles(1000);
+        assert!(smc.borrow().modifications().is_empty());
+    }
+}
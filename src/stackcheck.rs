@@ -0,0 +1,176 @@
+//! Stack overflow, underflow and corruption detection.
+//!
+//! StackChecker is a trace sink. It reports the stack operations (PUSH, POP, CALL, RET, RST and interrupts)
+//! which move the stack pointer out of the allowed range (once, until it comes back) : a deliberate stack
+//! switch with LXI SP or SPHL is not reported. It also reports the stack writes (PUSH, CALL, RST,
+//! interrupts) into a protected region, and the returns which pop an address which was not pushed by a
+//! call : the return address may have been overwritten, or the stack may be unbalanced.
+//! ```rust
+//! use intel8080::{CPU, stackcheck::{StackChecker, StackError}};
+//! use std::{cell::RefCell, rc::Rc};
+//! let mut c = CPU::new();
+//! c.sp = 0xff00;
+//! c.bus.write_byte(0x0000, 0xc5);     // PUSH B
+//! c.bus.write_byte(0x0001, 0xc3);     // JMP $0000
+//! let s = Rc::new(RefCell::new(StackChecker::new(0xfe00..=0xff00)));
+//! c.set_tracer(s.clone());
+//! c.run_cycles(6000);
+//! assert_eq!(s.borrow().errors(), &[StackError::Overflow { address: 0x0000, sp: 0xfdfe }]);
+//! assert_eq!(s.borrow().errors()[0].to_string(), "$0000 : stack overflow, SP = $fdfe");
+//! ```
+use std::{collections::HashMap, fmt, ops::RangeInclusive};
+use crate::{run::{above, is_call, is_ret}, step::AccessKind, trace::{TraceEvent, TraceSink}};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackError {
+    /// Stack pointer moved below the allowed range
+    Overflow { address: u16, sp: u16 },
+    /// Stack pointer moved above the allowed range
+    Underflow { address: u16, sp: u16 },
+    /// Stack write into a protected region
+    Protected { address: u16, target: u16 },
+    /// Return to an address which was not pushed by a call
+    Return { address: u16, target: u16 },
+}
+
+impl fmt::Display for StackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StackError::Overflow { address, sp } => write!(f, "${:04x} : stack overflow, SP = ${:04x}", address, sp),
+            StackError::Underflow { address, sp } => write!(f, "${:04x} : stack underflow, SP = ${:04x}", address, sp),
+            StackError::Protected { address, target } => write!(f, "${:04x} : stack write into protected memory at ${:04x}", address, target),
+            StackError::Return { address, target } => write!(f, "${:04x} : return to ${:04x}, which was not pushed by a call", address, target),
+        }
+    }
+}
+
+// PUSH and POP
+fn is_push_pop(opcode: u8) -> bool {
+    opcode & 0xCB == 0xC1
+}
+
+pub struct StackChecker {
+    bounds: RangeInclusive<u16>,
+    protected: Vec<RangeInclusive<u16>>,
+    errors: Vec<StackError>,
+    // Return addresses pushed by calls, by stack pointer
+    calls: HashMap<u16, u16>,
+    // Current instruction : address, opcode
+    current: Option<(u16, u8)>,
+    // Stack writes of the current instruction
+    writes: Vec<u16>,
+    // Stack pointer before the current instruction
+    sp: Option<u16>,
+}
+
+impl StackChecker {
+    /// `bounds` : allowed values of the stack pointer, from the full stack to the empty stack.
+    pub fn new(bounds: RangeInclusive<u16>) -> StackChecker {
+        StackChecker { bounds, protected: Vec::new(), errors: Vec::new(), calls: HashMap::new(), current: None, writes: Vec::new(), sp: None }
+    }
+
+    /// Stack writes into this region (code, data...) are reported.
+    pub fn protect(&mut self, region: RangeInclusive<u16>) {
+        self.protected.push(region);
+    }
+
+    pub fn errors(&self) -> &[StackError] {
+        &self.errors
+    }
+
+    /// Forgets the errors.
+    pub fn clear(&mut self) {
+        self.errors.clear();
+    }
+
+    fn registers(&mut self, pc: u16, sp: u16, stack: u16) {
+        if let (Some((address, opcode)), Some(before)) = (self.current.take(), self.sp) {
+            let call = is_call(opcode) && sp == before.wrapping_sub(2);
+            let ret = is_ret(opcode) && sp == before.wrapping_add(2);
+            if call {
+                self.calls.insert(sp, stack);
+            } else if ret && self.calls.remove(&before) != Some(pc) {
+                self.errors.push(StackError::Return { address, target: pc });
+            }
+            // Return addresses below the stack pointer have been popped or abandoned
+            if sp != before { self.calls.retain(|a, _| !above(sp, *a)) }
+            if (call || ret || is_push_pop(opcode)) && sp != before && self.bounds.contains(&before) {
+                if sp < *self.bounds.start() { self.errors.push(StackError::Overflow { address, sp }) }
+                if sp > *self.bounds.end() { self.errors.push(StackError::Underflow { address, sp }) }
+            }
+        }
+        self.sp = Some(sp);
+    }
+}
+
+impl TraceSink for StackChecker {
+    fn event(&mut self, event: &TraceEvent) {
+        match *event {
+            // The bus accesses of an instruction come before its Instruction event
+            TraceEvent::Bus(a) if a.kind == AccessKind::StackWrite => self.writes.push(a.address),
+            TraceEvent::Instruction { address, bytes, .. } => {
+                for target in std::mem::take(&mut self.writes) {
+                    if self.protected.iter().any(|r| r.contains(&target)) { self.errors.push(StackError::Protected { address, target }) }
+                }
+                self.current = Some((address, bytes[0]));
+            },
+            TraceEvent::Registers(r) => self.registers(r.pc, r.sp, r.stack),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{attach, cpu};
+
+    #[test]
+    fn returns() {
+        // $0000 : CALL $0010 / CALL $0020 / NOP / HLT
+        // $0010 : XTHL / XTHL / RET, $0020 : XTHL / INX H / XTHL / RET
+        let mut c = cpu(&[(0x0000, &[0xcd, 0x10, 0x00, 0xcd, 0x20, 0x00, 0x00, 0x76]), (0x0010, &[0xe3, 0xe3, 0xc9]),
+            (0x0020, &[0xe3, 0x23, 0xe3, 0xc9])]);
+        let s = attach(&mut c, StackChecker::new(0xfe00..=0xff00));
+        c.run_until(|c| c.halt);
+        assert_eq!(s.borrow().errors(), &[StackError::Return { address: 0x0023, target: 0x0007 }]);
+    }
+
+    #[test]
+    fn underflow() {
+        // $0000 : RET with an empty stack, then POP B in a loop
+        let mut c = cpu(&[(0x0000, &[0xc9]), (0x0004, &[0xc1, 0xc3, 0x04, 0x00]), (0xff00, &[0x04, 0x00])]);
+        let s = attach(&mut c, StackChecker::new(0xfe00..=0xff00));
+        c.run_cycles(100);
+        assert_eq!(s.borrow().errors(), &[
+            StackError::Return { address: 0x0000, target: 0x0004 },
+            StackError::Underflow { address: 0x0000, sp: 0xff02 },
+        ]);
+        assert_eq!(s.borrow().errors()[1].to_string(), "$0000 : stack underflow, SP = $ff02");
+    }
+
+    #[test]
+    fn protected() {
+        // $0000 : LXI SP,$0100 / CALL $0000
+        let mut c = cpu(&[(0x0000, &[0x31, 0x00, 0x01, 0xcd, 0x00, 0x00])]);
+        let s = attach(&mut c, StackChecker::new(0xfe00..=0xff00));
+        s.borrow_mut().protect(0x0000..=0x00ff);
+        c.execute();
+        c.execute();
+        // the stack switch is not an overflow
+        assert_eq!(s.borrow().errors(), &[
+            StackError::Protected { address: 0x0003, target: 0x00ff },
+            StackError::Protected { address: 0x0003, target: 0x00fe },
+        ]);
+    }
+
+    #[test]
+    fn stale_return_addresses() {
+        // $0000 : CALL $0010 / HLT, $0010 : LXI SP,$ff00 / LXI H,$0003 / PUSH H / RET
+        let mut c = cpu(&[(0x0000, &[0xcd, 0x10, 0x00, 0x76]), (0x0010, &[0x31, 0x00, 0xff, 0x21, 0x03, 0x00, 0xe5, 0xc9])]);
+        let s = attach(&mut c, StackChecker::new(0xfe00..=0xff00));
+        c.run_until(|c| c.halt);
+        // the return address pushed by the call was abandoned : $0003 comes from PUSH H
+        assert_eq!(s.borrow().errors(), &[StackError::Return { address: 0x0017, target: 0x0003 }]);
+    }
+}
@@ -0,0 +1,210 @@
+//! Detailed result of a single instruction.
+//! ```rust
+//! use intel8080::{CPU, step::{Access, AccessKind, StepKind}};
+//! let mut c = CPU::new();
+//! c.reg.set_hl(0x1000);
+//! c.bus.write_byte(0x0000, 0x34);     // INR (HL)
+//! let s = c.step();
+//! assert_eq!(s.kind, StepKind::Instruction);
+//! assert_eq!(s.instruction, "34        INR (HL)");
+//! assert_eq!(s.cycles, 10);
+//! assert_eq!(s.accesses, vec![Access { kind: AccessKind::Fetch, address: 0x0000, value: 0x34 },
+//!     Access { kind: AccessKind::Read, address: 0x1000, value: 0 },
+//!     Access { kind: AccessKind::Write, address: 0x1000, value: 1 }]);
+//! ```
+use crate::{CPU, dasm::disassemble_or_data, length};
+
+/// What a step did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepKind {
+    /// An instruction has been fetched from memory and executed.
+    Instruction,
+    /// An interrupt has been accepted : the instruction supplied by the interrupting device has been executed.
+    Interrupt,
+    /// The CPU is halted : no instruction has been executed.
+    Halted,
+}
+
+/// Memory or I/O access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    /// Opcode or operand fetch.
+    Fetch,
+    /// Memory read.
+    Read,
+    /// Memory write.
+    Write,
+    /// Stack read : POP, RET, XTHL.
+    StackRead,
+    /// Stack write : PUSH, CALL, RST, XTHL.
+    StackWrite,
+    /// Input port read.
+    In,
+    /// Output port write.
+    Out,
+    /// Memory written by a DMA device while the CPU was stalled, before the instruction.
+    DmaWrite,
+}
+
+/// A byte read or written by an instruction. The address of an I/O access is the port number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Access {
+    pub kind: AccessKind,
+    pub address: u16,
+    pub value: u8,
+}
+
+/// Result of CPU::step().
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    /// Address of the instruction (pc before the step).
+    pub address: u16,
+    /// Opcode and operands. Empty if no instruction has been executed.
+    pub bytes: Vec<u8>,
+    /// Disassembled instruction, "DB $xx" for an undefined opcode.
+    pub instruction: String,
+    /// Clock cycles consumed, including DMA stalls and fast-forwarded idle loops.
+    pub cycles: u32,
+    pub kind: StepKind,
+    /// The CPU is halted after the step.
+    pub halted: bool,
+    /// The opcode is not a documented 8080 instruction (executed as a NOP).
+    pub undefined: bool,
+    /// Memory and I/O accesses, in bus order.
+    pub accesses: Vec<Access>,
+}
+
+// Summary of an execute() call
+pub(crate) struct Executed {
+    pub(crate) address: u16,
+    pub(crate) bytes: [u8; 3],
+    pub(crate) cycles: u32,
+    pub(crate) kind: StepKind,
+}
+
+/// Returns true for the opcodes which are not documented 8080 instructions.
+pub fn undefined(opcode: u8) -> bool {
+    matches!(opcode, 0x08 | 0x10 | 0x18 | 0x20 | 0x28 | 0x30 | 0x38 | 0xCB | 0xD9 | 0xDD | 0xED | 0xFD)
+}
+
+impl CPU {
+    /// Enables or disables the recording of the memory and I/O accesses of each instruction.
+    /// The bytes written by DMA devices before the instruction are recorded as DmaWrite accesses. Disabled by default.
+    /// ```rust
+    /// use intel8080::{CPU, step::{Access, AccessKind}};
+    /// let mut c = CPU::new();
+    /// c.set_access_log(true);
+    /// c.bus.write_byte(0x0000, 0x3a);     // LDA $1000
+    /// c.bus.write_word(0x0001, 0x1000);
+    /// c.execute();
+    /// assert_eq!(c.accesses().len(), 4);
+    /// assert_eq!(c.accesses()[3], Access { kind: AccessKind::Read, address: 0x1000, value: 0 });
+    /// ```
+    pub fn set_access_log(&mut self, enabled: bool) {
+        self.recording = enabled;
+        self.accesses.clear();
+    }
+
+    /// Memory and I/O accesses of the last instruction, in bus order. Empty if recording is disabled.
+    pub fn accesses(&self) -> &[Access] {
+        &self.accesses
+    }
+
+    /// Executes one instruction, like execute(), and returns what it did.
+    /// Its accesses are recorded even if the access log is disabled.
+    pub fn step(&mut self) -> Step {
+        let recording = std::mem::replace(&mut self.recording, true);
+        let e = self.exec();
+        self.recording = recording;
+        let accesses = if recording { self.accesses.clone() } else { std::mem::take(&mut self.accesses) };
+        let len = if e.kind == StepKind::Halted { 0 } else { usize::from(length(e.bytes[0])) };
+        Step {
+            address: e.address,
+            bytes: e.bytes[..len].to_vec(),
+            instruction: if len == 0 { String::new() } else { disassemble_or_data(e.bytes) },
+            cycles: e.cycles,
+            kind: e.kind,
+            halted: self.halt,
+            undefined: len != 0 && undefined(e.bytes[0]),
+            accesses,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::cpu;
+
+    #[test]
+    fn instruction() {
+        let mut c = cpu(&[(0x0000, &[0xcd, 0x34, 0x12])]);     // CALL $1234
+        let s = c.step();
+        assert_eq!(s.address, 0x0000);
+        assert_eq!(s.bytes, vec![0xcd, 0x34, 0x12]);
+        assert_eq!(s.instruction, "CD 3412   CALL $1234");
+        assert_eq!(s.cycles, 17);
+        assert_eq!(s.accesses[3..], [Access { kind: AccessKind::StackWrite, address: 0xfeff, value: 0x00 },
+            Access { kind: AccessKind::StackWrite, address: 0xfefe, value: 0x03 }]);
+        assert!(!s.halted && !s.undefined);
+    }
+
+    #[test]
+    fn io() {
+        // OUT $10 / IN $20
+        let mut c = cpu(&[(0x0000, &[0xd3, 0x10, 0xdb, 0x20])]);
+        c.reg.a = 0x55;
+        assert_eq!(c.step().accesses[2], Access { kind: AccessKind::Out, address: 0x10, value: 0x55 });
+        assert_eq!(c.step().accesses[2], Access { kind: AccessKind::In, address: 0x20, value: 0x55 });
+    }
+
+    #[test]
+    fn interrupt_and_halt() {
+        let mut c = cpu(&[(0x0000, &[0x76])]);                 // HLT
+        let s = c.step();
+        assert_eq!((s.kind, s.halted, s.cycles), (StepKind::Instruction, true, 7));
+        let s = c.step();
+        assert_eq!((s.kind, s.halted, s.cycles, s.bytes.len()), (StepKind::Halted, true, 4, 0));
+        c.inte = true;
+        c.int = (true, 0xd7);                           // RST 2
+        let s = c.step();
+        assert_eq!((s.kind, s.address, s.halted), (StepKind::Interrupt, 0x0001, false));
+        assert_eq!(s.bytes, vec![0xd7]);
+        assert_eq!(c.pc, 0x0010);
+    }
+
+    #[test]
+    fn undefined_opcode() {
+        let mut c = cpu(&[(0x0000, &[0xdd])]);
+        let s = c.step();
+        assert!(s.undefined);
+        assert_eq!(s.instruction, "DD        DB $dd");
+        assert_eq!(c.pc, 0x0001);
+    }
+
+    #[test]
+    fn access_log() {
+        // PUSH B / POP PSW
+        let mut c = cpu(&[(0x0000, &[0xc5, 0xf1])]);
+        c.reg.set_bc(0x1234);
+        c.execute();
+        assert!(c.accesses().is_empty());
+        c.set_access_log(true);
+        c.pc = 0x0000;
+        c.execute();
+        assert_eq!(c.accesses(), [Access { kind: AccessKind::Fetch, address: 0x0000, value: 0xc5 },
+            Access { kind: AccessKind::StackWrite, address: 0xfefd, value: 0x12 },
+            Access { kind: AccessKind::StackWrite, address: 0xfefc, value: 0x34 }]);
+        let s = c.step();
+        assert_eq!(c.accesses(), s.accesses);
+        // low byte (flags) first, like POP B
+        assert_eq!(s.accesses[1..], [Access { kind: AccessKind::StackRead, address: 0xfefc, value: 0x34 },
+            Access { kind: AccessKind::StackRead, address: 0xfefd, value: 0x12 }]);
+        // interrupt : the instruction is not fetched from memory
+        c.inte = true;
+        c.int = (true, 0xff);
+        c.execute();
+        assert_eq!(c.accesses().iter().filter(|a| a.kind == AccessKind::Fetch).count(), 0);
+        assert_eq!(c.accesses().len(), 2);
+    }
+}
@@ -1,4 +1,10 @@
+#![allow(clippy::bool_assert_comparison)]
+
 use crate::CPU;
+use crate::interrupt::{InterruptController, InterruptLines};
+use crate::memory::IoDevice;
+use crate::i8259::I8259;
+use std::{cell::RefCell, rc::Rc};
 
 #[test]
 fn ldax_b() {
@@ -2065,3 +2071,322 @@ fn rom_space_word() {
     assert_eq!(c.bus.read_word(0xffee), 0x3e3e);
     assert_eq!(c.bus.read_byte(0xfff0), 0);
 }
+
+struct IntCall {
+    pending: bool,
+    bytes: [u8; 3],
+}
+
+impl InterruptController for IntCall {
+    fn int(&self) -> bool {
+        self.pending
+    }
+
+    fn inta(&mut self, n: u8) -> u8 {
+        if n == 2 { self.pending = false }
+        self.bytes[usize::from(n)]
+    }
+}
+
+#[test]
+fn int_rst() {
+    let mut c = CPU::new();
+    c.pc = 0x0100;
+    c.sp = 0xff00;
+    c.inte = true;
+    c.int = (true, 0xcf);
+    assert_eq!(c.execute(), 11);
+    assert_eq!(c.pc, 0x0008);
+    assert_eq!(c.sp, 0xfefe);
+    assert_eq!(c.bus.read_word(0xfefe), 0x0100);
+    assert_eq!(c.inte, false);
+    assert_eq!(c.int, (false, 0));
+}
+
+#[test]
+fn int_call() {
+    let mut c = CPU::new();
+    c.pc = 0x0100;
+    c.sp = 0xff00;
+    c.inte = true;
+    c.int_controller = Some(Box::new(IntCall { pending: true, bytes: [0xcd, 0x34, 0x12] }));
+    assert_eq!(c.execute(), 17);
+    assert_eq!(c.pc, 0x1234);
+    assert_eq!(c.sp, 0xfefe);
+    assert_eq!(c.bus.read_word(0xfefe), 0x0100);
+    assert_eq!(c.inte, false);
+    assert_eq!(c.int_controller.as_ref().unwrap().int(), false);
+}
+
+#[test]
+fn int_single_byte() {
+    let mut c = CPU::new();
+    c.pc = 0x0100;
+    c.inte = true;
+    c.reg.a = 0x10;
+    c.int = (true, 0x3c);                           // INR A
+    c.execute();
+    assert_eq!(c.reg.a, 0x11);
+    assert_eq!(c.pc, 0x0100);
+}
+
+#[test]
+fn int_disabled() {
+    let mut c = CPU::new();
+    c.pc = 0x0100;
+    c.bus.write_byte(0x0100, 0x3c);                 // INR A
+    c.int_controller = Some(Box::new(IntCall { pending: true, bytes: [0xcd, 0x34, 0x12] }));
+    c.execute();
+    assert_eq!(c.reg.a, 0x01);
+    assert_eq!(c.pc, 0x0101);
+    assert_eq!(c.int_controller.as_ref().unwrap().int(), true);
+}
+
+#[test]
+fn int_lines() {
+    let lines = Rc::new(RefCell::new(InterruptLines::new(8)));
+    let mut c = CPU::new();
+    c.int_controller = Some(Box::new(lines.clone()));
+    c.pc = 0x0100;
+    c.sp = 0xff00;
+    c.inte = true;
+    lines.borrow_mut().assert_rst(6, 6);
+    lines.borrow_mut().assert_rst(1, 1);
+    c.execute();
+    assert_eq!(c.pc, 0x0008);
+    assert_eq!(lines.borrow().is_asserted(1), false);
+    assert_eq!(lines.borrow().is_asserted(6), true);
+    c.inte = true;
+    c.execute();
+    assert_eq!(c.pc, 0x0030);
+    assert_eq!(c.bus.read_word(c.sp), 0x0008);
+}
+
+struct Port(u8);
+
+impl IoDevice for Port {
+    fn input(&mut self, _port: u8) -> u8 {
+        self.0
+    }
+
+    fn output(&mut self, _port: u8, data: u8) {
+        self.0 = data
+    }
+}
+
+#[test]
+fn in_out() {
+    let mut c = CPU::new();
+    c.bus.attach_io(0x10..=0x10, Port(0x42));
+    c.bus.write_byte(0x0000, 0xdb);                 // IN $10
+    c.bus.write_byte(0x0001, 0x10);
+    c.bus.write_byte(0x0002, 0x3c);                 // INR A
+    c.bus.write_byte(0x0003, 0xd3);                 // OUT $10
+    c.bus.write_byte(0x0004, 0x10);
+    c.bus.write_byte(0x0005, 0xdb);                 // IN $11
+    c.bus.write_byte(0x0006, 0x11);
+    c.execute();
+    assert_eq!(c.pc, 2);
+    assert_eq!(c.reg.a, 0x42);
+    c.execute();
+    c.execute();
+    assert_eq!(c.bus.io_read(0x10), Some(0x43));
+    c.execute();
+    assert_eq!(c.reg.a, 0x43);
+    assert_eq!(c.pc, 7);
+}
+
+#[test]
+fn int_i8259() {
+    let pic = Rc::new(RefCell::new(I8259::new()));
+    let mut c = CPU::new();
+    c.bus.attach_io(0x20..=0x21, pic.clone());
+    c.int_controller = Some(Box::new(pic.clone()));
+    // MVI A,$16 / OUT $20 / MVI A,$10 / OUT $21 / EI / NOP
+    for (i, b) in [0x3e, 0x16, 0xd3, 0x20, 0x3e, 0x10, 0xd3, 0x21, 0xfb, 0x00].iter().enumerate() {
+        c.bus.write_byte(i as u16, *b);
+    }
+    c.sp = 0xff00;
+    for _ in 0..6 { c.execute(); }
+    pic.borrow_mut().set_ir(2, true);
+    assert_eq!(c.execute(), 17);
+    assert_eq!(c.pc, 0x1008);
+    assert_eq!(c.bus.read_word(c.sp), 0x000a);
+    assert_eq!(pic.borrow().isr, 0x04);
+}
+
+#[test]
+fn hlt_idle() {
+    let mut c = CPU::new();
+    c.bus.write_byte(0x0000, 0x76);
+    c.int = (true, 0xcf);
+    c.execute();
+    assert_eq!(c.execute(), 4);
+    assert_eq!(c.halt, true);
+    assert_eq!(c.pc, 1);
+}
+
+#[test]
+fn hlt_interrupt() {
+    let mut c = CPU::new();
+    c.sp = 0xff00;
+    c.bus.write_byte(0x0000, 0xfb);                 // EI
+    c.bus.write_byte(0x0001, 0x76);                 // HLT
+    c.execute();
+    c.execute();
+    assert_eq!(c.halt, true);
+    assert_eq!(c.execute(), 4);
+    assert_eq!(c.execute(), 4);
+    c.int = (true, 0xd7);
+    assert_eq!(c.execute(), 11);
+    assert_eq!(c.halt, false);
+    assert_eq!(c.pc, 0x0010);
+    assert_eq!(c.bus.read_word(c.sp), 0x0002);
+}
+
+#[test]
+fn ei_delay() {
+    let mut c = CPU::new();
+    c.sp = 0xff00;
+    c.bus.write_byte(0x0000, 0xfb);                 // EI
+    c.bus.write_byte(0x0001, 0x3c);                 // INR A
+    c.bus.write_byte(0x0002, 0x3c);                 // INR A
+    c.int = (true, 0xcf);
+    c.execute();
+    assert_eq!(c.inte, true);
+    c.execute();
+    assert_eq!(c.reg.a, 1);
+    assert_eq!(c.pc, 0x0002);
+    c.execute();
+    assert_eq!(c.reg.a, 1);
+    assert_eq!(c.pc, 0x0008);
+    assert_eq!(c.bus.read_word(c.sp), 0x0002);
+}
+
+#[test]
+fn ei_ret() {
+    let mut c = CPU::new();
+    // Interrupt handler ending with EI / RET, interrupted program at $0100
+    c.bus.write_byte(0x0008, 0xfb);                 // EI
+    c.bus.write_byte(0x0009, 0xc9);                 // RET
+    c.pc = 0x0008;
+    c.sp = 0xfefe;
+    c.bus.write_word(0xfefe, 0x0100);
+    c.int = (true, 0xcf);
+    c.execute();
+    c.execute();
+    // RET has been executed before the new interrupt is accepted : the stack does not grow
+    assert_eq!(c.pc, 0x0100);
+    assert_eq!(c.sp, 0xff00);
+    c.execute();
+    assert_eq!(c.pc, 0x0008);
+    assert_eq!(c.sp, 0xfefe);
+    assert_eq!(c.bus.read_word(0xfefe), 0x0100);
+}
+
+#[test]
+fn ei_hlt() {
+    let mut c = CPU::new();
+    c.sp = 0xff00;
+    c.bus.write_byte(0x0000, 0xfb);                 // EI
+    c.bus.write_byte(0x0001, 0x76);                 // HLT
+    c.int = (true, 0xcf);
+    c.execute();
+    c.execute();
+    // HLT has been executed before the interrupt is accepted
+    assert_eq!(c.halt, true);
+    assert_eq!(c.execute(), 11);
+    assert_eq!(c.halt, false);
+    assert_eq!(c.pc, 0x0008);
+    assert_eq!(c.bus.read_word(c.sp), 0x0002);
+}
+
+#[test]
+fn interrupt_asm() {
+    let mut c = CPU::new();
+    // examples/interrupt.asm
+    for (i, b) in [0x31, 0x00, 0xff, 0x3e, 0x0f, 0xc3, 0x00, 0x97].iter().enumerate() {
+        c.bus.write_byte(i as u16, *b);
+    }
+    c.bus.write_byte(0x0008, 0x47);                 // MOV B,A
+    c.bus.write_byte(0x0009, 0xc9);                 // RET
+    for (i, b) in [0xfb, 0xb8, 0xc2, 0x01, 0x97, 0xc9].iter().enumerate() {
+        c.bus.write_byte(0x9700 + i as u16, *b);
+    }
+    c.int = (true, 0xcf);
+    // LXI SP / MVI A / JMP / EI / CMP B
+    for _ in 0..5 { c.execute(); }
+    assert_eq!(c.pc, 0x9702);
+    // RST 1 / MOV B,A / RET
+    c.execute();
+    assert_eq!(c.pc, 0x0008);
+    assert_eq!(c.bus.read_word(c.sp), 0x9702);
+    c.execute();
+    c.execute();
+    assert_eq!(c.pc, 0x9702);
+    assert_eq!(c.reg.b, 0x0f);
+    // JNZ / CMP B / JNZ / RET
+    for _ in 0..4 { c.execute(); }
+    assert_eq!(c.pc, 0x0000);
+}
+
+#[test]
+fn virtual_clock() {
+    let mut c = CPU::new();
+    c.set_freq(1.0);
+    c.bus.write_byte(0x0000, 0x3e);                 // MVI A,$01
+    c.bus.write_byte(0x0001, 0x01);
+    c.bus.write_byte(0x0002, 0x76);                 // HLT
+    c.execute();
+    c.execute();
+    c.execute();
+    assert_eq!(c.cycles(), 18);
+    assert_eq!(c.emulated_time().as_micros(), 18);
+}
+
+#[test]
+fn pause_resume() {
+    let mut c = CPU::new();
+    c.pause();
+    assert_eq!(c.execute_timed(), Some(16));
+    assert_eq!(c.execute_throttled(), 0);
+    assert_eq!(c.pc, 0);
+    c.resume();
+    assert_eq!(c.execute_throttled(), 4);
+    assert_eq!(c.pc, 1);
+}
+
+#[test]
+fn turbo() {
+    let mut c = CPU::new();
+    c.set_turbo(true);
+    for _ in 0..100000 { assert_eq!(c.execute_timed(), None); }
+}
+
+#[test]
+fn turbo_slices() {
+    let mut c = CPU::new();
+    c.set_turbo(true);
+    c.set_idle_detection(true);
+    c.set_slice_duration(1);                        // 2100 cycles
+    c.bus.write_byte(0x0000, 0xc3);                 // JMP $0000
+    c.schedule(1_000_000, |_| None);
+    // idle loops are still fast-forwarded to the end of each slice
+    for _ in 0..100 {
+        assert_eq!(c.execute_timed(), None);
+        assert!(c.slice_current_cycles <= c.slice_max_cycles + 10);
+    }
+    assert!(c.cycles() > 40 * 2100);
+}
+
+#[test]
+fn throttled() {
+    let mut c = CPU::new();
+    c.set_freq(1.0);
+    c.set_speed(0.5);
+    c.set_slice_duration(2);
+    let start = std::time::Instant::now();
+    // 10ms of emulated time at half speed
+    while c.cycles() < 10000 { c.execute_throttled(); }
+    assert!(start.elapsed().as_millis() >= 12);
+}
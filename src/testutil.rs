@@ -0,0 +1,20 @@
+// Helpers shared by the unit tests
+use crate::{trace::TraceSink, CPU};
+use std::{cell::RefCell, rc::Rc};
+
+// CPU with the stack at $ff00 and a program : blocks of bytes and their addresses
+pub(crate) fn cpu(program: &[(u16, &[u8])]) -> CPU {
+    let mut c = CPU::new();
+    c.sp = 0xff00;
+    for (org, bytes) in program {
+        for (n, b) in bytes.iter().enumerate() { c.bus.write_byte(org.wrapping_add(n as u16), *b); }
+    }
+    c
+}
+
+// Attaches a trace sink and returns a handle on it. The sink gets the current registers : set them up before.
+pub(crate) fn attach<T: TraceSink + 'static>(c: &mut CPU, sink: T) -> Rc<RefCell<T>> {
+    let sink = Rc::new(RefCell::new(sink));
+    c.set_tracer(sink.clone());
+    sink
+}
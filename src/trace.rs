@@ -0,0 +1,292 @@
+//! Execution trace : the CPU sends events to a sink while it executes instructions.
+//!
+//! Events of an instruction are sent in this order : Hold (if DMA devices used the bus before it), Interrupt (if
+//! the instruction has been supplied by an interrupting device), Bus and In / Out, InterruptEnable / Halt,
+//! Instruction, Registers.
+//! ```rust
+//! use intel8080::{CPU, trace::{TraceFormat, TraceWriter}};
+//! use std::{cell::RefCell, rc::Rc};
+//! let mut c = CPU::new();
+//! let trace = Rc::new(RefCell::new(TraceWriter::new(Vec::new(), TraceFormat::Compact)));
+//! c.set_tracer(trace.clone());
+//! c.bus.write_byte(0x0000, 0x3e);     // MVI A,$0f
+//! c.bus.write_byte(0x0001, 0x0f);
+//! c.execute();
+//! let out = String::from_utf8(trace.borrow().get_ref().clone()).unwrap();
+//! assert_eq!(out, "0000  3E 0f     MVI A,$0f          A:0F F:02 BC:0000 DE:0000 HL:0000 SP:0000 CYC:0\n");
+//! ```
+use std::{cell::RefCell, fs::File, io::{self, BufWriter, Write}, path::Path, rc::Rc};
+use crate::{CPU, disassemble, step::Access};
+
+/// CPU registers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterState {
+    pub pc: u16,
+    pub sp: u16,
+    pub a: u8,
+    /// Flags byte, as pushed by PUSH PSW
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub inte: bool,
+    /// Word at (SP)
+    pub stack: u16,
+    /// Memory at PC : the next instruction
+    pub code: [u8; 4],
+}
+
+/// Something the CPU did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceEvent {
+    /// An instruction has been executed. `clock` is the virtual clock when its execution started, after
+    /// the DMA transfers. `cycles` includes the DMA transfers.
+    Instruction { address: u16, bytes: [u8; 3], cycles: u32, clock: u64 },
+    /// Registers after the instruction. Also sent when the sink is attached.
+    Registers(RegisterState),
+    /// Input port read.
+    In { port: u8, value: u8 },
+    /// Output port write.
+    Out { port: u8, value: u8 },
+    /// Memory or I/O bus cycle, in bus order.
+    Bus(Access),
+    /// DMA devices held the bus (HOLD / HLDA) from `clock`.
+    Hold { clock: u64, cycles: u32 },
+    /// An interrupt has been accepted at `address` : the device supplied the instruction bytes.
+    Interrupt { address: u16, bytes: [u8; 3] },
+    /// EI (true) or DI (false).
+    InterruptEnable(bool),
+    /// HLT
+    Halt { address: u16 },
+    /// Memory read or fetched before it was ever written (see Bus::track_writes()), sent after its Bus event.
+    Uninitialized(Access),
+}
+
+/// Receives the trace events.
+pub trait TraceSink {
+    fn event(&mut self, event: &TraceEvent);
+}
+
+/// A shared sink : the caller keeps a handle on it.
+impl<T: TraceSink> TraceSink for Rc<RefCell<T>> {
+    fn event(&mut self, event: &TraceEvent) {
+        self.borrow_mut().event(event)
+    }
+}
+
+/// Two sinks receiving the same events, for instance a profiler and a trace file.
+impl<A: TraceSink, B: TraceSink> TraceSink for (A, B) {
+    fn event(&mut self, event: &TraceEvent) {
+        self.0.event(event);
+        self.1.event(event);
+    }
+}
+
+/// Text formats of TraceWriter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// Disassembled instruction followed by the registers on two lines
+    /// ```text
+    /// 3E 0f     MVI A,$0f
+    /// PC : 0x0000    SP : 0xff00    S : 0    Z : 0    A : 0    P : 0    C : 0
+    /// B : 0x00    C : 0x00    D : 0x00    E : 0x00    H : 0x00    L : 0x00 ...
+    /// ```
+    Text,
+    /// One line per instruction : address, disassembled instruction, registers after it, virtual clock before it
+    /// ```text
+    /// 0000  3E 0f     MVI A,$0f          A:0F F:02 BC:0000 DE:0000 HL:0000 SP:0000 CYC:0
+    /// ```
+    Compact,
+    /// Format of the superzazu/8080 core : registers, virtual clock and memory at PC before each instruction.
+    /// The memory bytes are preceded by a tab.
+    /// ```text
+    /// PC: 0100, AF: 0002, BC: 0000, DE: 0000, HL: 0000, SP: 0000, CYC: 0    (31 00 00 00)
+    /// ```
+    Superzazu,
+    /// Format of the "doctor" trace checkers : registers and memory at PC before each instruction
+    /// ```text
+    /// A:00 F:02 B:00 C:00 D:00 E:00 H:00 L:00 SP:0000 PC:0100 PCMEM:31,00,00,00
+    /// ```
+    Doctor,
+}
+
+/// Writes the executed instructions to any writer (stdout, file, memory buffer) in a text format.
+pub struct TraceWriter<W: Write> {
+    out: W,
+    format: TraceFormat,
+    // Instruction waiting for the registers event
+    pending: Option<(u16, [u8; 3], u64)>,
+    // Registers before the next instruction
+    state: Option<RegisterState>,
+}
+
+impl TraceWriter<BufWriter<File>> {
+    /// Creates (or truncates) a trace file.
+    pub fn file(path: impl AsRef<Path>, format: TraceFormat) -> io::Result<Self> {
+        Ok(TraceWriter::new(BufWriter::new(File::create(path)?), format))
+    }
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(out: W, format: TraceFormat) -> TraceWriter<W> {
+        TraceWriter { out, format, pending: None, state: None }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.out
+    }
+
+    /// Returns the writer.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn line(&self, (address, bytes, clock): (u16, [u8; 3], u64), r: &RegisterState) -> String {
+        match self.format {
+            TraceFormat::Text => format!("{}\nPC : {:#06x}\tSP : {:#06x}\tS : {}\tZ : {}\tA : {}\tP : {}\tC : {}\nB : {:#04x}\tC : {:#04x}\tD : {:#04x}\tE : {:#04x}\tH : {:#04x}\tL : {:#04x}\tA : {:#04x}\t(SP) : {:#06x}\n",
+                disassemble(bytes), address, r.sp, r.f >> 7 & 1, r.f >> 6 & 1, r.f >> 4 & 1, r.f >> 2 & 1, r.f & 1,
+                r.b, r.c, r.d, r.e, r.h, r.l, r.a, r.stack),
+            TraceFormat::Compact => format!("{:04X}  {:<28} A:{:02X} F:{:02X} BC:{:02X}{:02X} DE:{:02X}{:02X} HL:{:02X}{:02X} SP:{:04X} CYC:{}",
+                address, disassemble(bytes), r.a, r.f, r.b, r.c, r.d, r.e, r.h, r.l, r.sp, clock),
+            TraceFormat::Superzazu => format!("PC: {:04X}, AF: {:02X}{:02X}, BC: {:02X}{:02X}, DE: {:02X}{:02X}, HL: {:02X}{:02X}, SP: {:04X}, CYC: {}\t({:02X} {:02X} {:02X} {:02X})",
+                r.pc, r.a, r.f, r.b, r.c, r.d, r.e, r.h, r.l, r.sp, clock, r.code[0], r.code[1], r.code[2], r.code[3]),
+            TraceFormat::Doctor => format!("A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+                r.a, r.f, r.b, r.c, r.d, r.e, r.h, r.l, r.sp, r.pc, r.code[0], r.code[1], r.code[2], r.code[3]),
+        }
+    }
+
+    fn write(&mut self, line: String) {
+        // A trace is a diagnostic output : a write error must not stop the emulation
+        let _ = writeln!(self.out, "{}", line);
+    }
+}
+
+impl<W: Write> TraceSink for TraceWriter<W> {
+    fn event(&mut self, event: &TraceEvent) {
+        let before = matches!(self.format, TraceFormat::Superzazu | TraceFormat::Doctor);
+        match *event {
+            // Reference formats : registers before the instruction
+            TraceEvent::Instruction { address, bytes, clock, .. } if before => if let Some(r) = self.state {
+                let line = self.line((address, bytes, clock), &r);
+                self.write(line);
+            },
+            TraceEvent::Instruction { address, bytes, clock, .. } => self.pending = Some((address, bytes, clock)),
+            TraceEvent::Registers(r) if before => self.state = Some(r),
+            TraceEvent::Registers(r) => if let Some(i) = self.pending.take() {
+                let line = self.line(i, &r);
+                self.write(line);
+            },
+            _ => {}
+        }
+    }
+}
+
+impl CPU {
+    /// Sends the trace events to a sink, replacing the previous one. The current registers are sent at once.
+    pub fn set_tracer(&mut self, sink: impl TraceSink + 'static) {
+        let mut sink = Box::new(sink);
+        sink.event(&TraceEvent::Registers(self.register_state()));
+        self.tracer = Some(sink);
+    }
+
+    /// Stops tracing, returns the sink.
+    pub fn remove_tracer(&mut self) -> Option<Box<dyn TraceSink>> {
+        self.tracer.take()
+    }
+
+    /// Returns the registers.
+    pub fn register_state(&self) -> RegisterState {
+        RegisterState {
+            pc: self.pc, sp: self.sp, a: self.reg.a, f: self.flags.as_byte(),
+            b: self.reg.b, c: self.reg.c, d: self.reg.d, e: self.reg.e, h: self.reg.h, l: self.reg.l,
+            inte: self.inte,
+            stack: u16::from_le_bytes([self.bus.read_byte(self.sp), self.bus.read_byte(self.sp.wrapping_add(1))]),
+            code: [0, 1, 2, 3].map(|n| self.bus.read_byte(self.pc.wrapping_add(n))),
+        }
+    }
+
+    pub(crate) fn trace(&mut self, event: TraceEvent) {
+        if let Some(t) = self.tracer.as_mut() { t.event(&event) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{step::AccessKind, testutil::{attach, cpu}};
+
+    #[derive(Default)]
+    struct Events(Vec<TraceEvent>);
+
+    impl TraceSink for Events {
+        fn event(&mut self, event: &TraceEvent) { self.0.push(*event) }
+    }
+
+    #[test]
+    fn events() {
+        // EI / OUT $10 / HLT
+        let mut c = cpu(&[(0x0000, &[0xfb, 0xd3, 0x10, 0x76])]);
+        c.reg.a = 0x12;
+        let events = attach(&mut c, Events::default());
+        for _ in 0..3 { c.execute(); }
+        c.int = (true, 0xcf);                           // RST 1
+        c.execute();
+        let e: Vec<TraceEvent> = events.borrow().0.iter().filter(|e| !matches!(e, TraceEvent::Bus(_))).cloned().collect();
+        assert!(events.borrow().0.contains(&TraceEvent::Bus(Access { kind: AccessKind::StackWrite, address: 0xfefe, value: 0x04 })));
+        assert!(matches!(e[0], TraceEvent::Registers(RegisterState { pc: 0x0000, .. })));
+        assert_eq!(e[1], TraceEvent::InterruptEnable(true));
+        assert_eq!(e[2], TraceEvent::Instruction { address: 0x0000, bytes: [0xfb, 0, 0], cycles: 4, clock: 0 });
+        assert_eq!(e[4], TraceEvent::Out { port: 0x10, value: 0x12 });
+        assert_eq!(e[5], TraceEvent::Instruction { address: 0x0001, bytes: [0xd3, 0x10, 0], cycles: 10, clock: 4 });
+        assert_eq!(e[7], TraceEvent::Halt { address: 0x0003 });
+        assert_eq!(e[10], TraceEvent::Interrupt { address: 0x0004, bytes: [0xcf, 0, 0] });
+        assert!(matches!(e[12], TraceEvent::Registers(RegisterState { pc: 0x0008, sp: 0xfefe, stack: 0x0004, inte: false, .. })));
+        assert_eq!(e.len(), 13);
+    }
+
+    #[test]
+    fn text() {
+        let mut c = cpu(&[(0x0000, &[0x37])]);                 // STC
+        let trace = attach(&mut c, TraceWriter::new(Vec::new(), TraceFormat::Text));
+        c.execute();
+        c.remove_tracer();
+        c.execute();
+        let out = String::from_utf8(trace.borrow().get_ref().clone()).unwrap();
+        assert_eq!(out, "37        STC\nPC : 0x0000\tSP : 0xff00\tS : 0\tZ : 0\tA : 0\tP : 0\tC : 1\n\
+            B : 0x00\tC : 0x00\tD : 0x00\tE : 0x00\tH : 0x00\tL : 0x00\tA : 0x00\t(SP) : 0x0000\n\n");
+    }
+
+    #[test]
+    fn file() {
+        let path = std::env::temp_dir().join(format!("intel8080_trace_{}.txt", std::process::id()));
+        let mut c = cpu(&[]);
+        c.set_tracer(TraceWriter::file(&path, TraceFormat::Compact).unwrap());
+        c.run_cycles(12);
+        drop(c.remove_tracer());
+        let out = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(out.lines().count(), 3);
+        assert!(out.lines().nth(2).unwrap().starts_with("0002  00        NOP"));
+        assert!(out.ends_with("CYC:8\n"));
+    }
+
+    #[test]
+    fn reference_formats() {
+        // $0100 : LXI SP,$fe00 / MVI A,$80
+        let mut c = cpu(&[(0x0100, &[0x31, 0x00, 0xfe, 0x3e, 0x80])]);
+        c.pc = 0x0100;
+        let superzazu = attach(&mut c, TraceWriter::new(Vec::new(), TraceFormat::Superzazu));
+        c.execute();
+        c.execute();
+        let doctor = attach(&mut c, TraceWriter::new(Vec::new(), TraceFormat::Doctor));
+        c.execute();
+        let out = String::from_utf8(superzazu.borrow().get_ref().clone()).unwrap();
+        assert_eq!(out, "PC: 0100, AF: 0002, BC: 0000, DE: 0000, HL: 0000, SP: FF00, CYC: 0\t(31 00 FE 3E)\n\
+            PC: 0103, AF: 0002, BC: 0000, DE: 0000, HL: 0000, SP: FE00, CYC: 10\t(3E 80 00 00)\n");
+        let out = String::from_utf8(doctor.borrow().get_ref().clone()).unwrap();
+        assert_eq!(out, "A:80 F:02 B:00 C:00 D:00 E:00 H:00 L:00 SP:FE00 PC:0105 PCMEM:00,00,00,00\n");
+    }
+}
@@ -0,0 +1,95 @@
+//! Compares two traces line by line, and reports the first divergent instruction.
+//!
+//! Both traces must use the same one-line format, for instance TraceFormat::Superzazu for a comparison
+//! with the superzazu/8080 core. Trailing whitespace is ignored.
+//! ```rust
+//! use intel8080::tracediff::diff;
+//! let ours = "PC: 0000, AF: 0002\nPC: 0001, AF: 0002\nPC: 0002, AF: 0102\n";
+//! let reference = "PC: 0000, AF: 0002\nPC: 0001, AF: 0002\nPC: 0002, AF: 0002\n";
+//! let d = diff(ours.as_bytes(), reference.as_bytes(), 1).unwrap().unwrap();
+//! assert_eq!(d.line, 3);
+//! assert_eq!(d.before, vec!["PC: 0001, AF: 0002"]);
+//! ```
+use std::{collections::VecDeque, fmt, io::{self, BufRead}};
+
+/// First divergence between two traces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Line number of the first divergent line, starting at 1
+    pub line: usize,
+    /// Identical lines preceding the divergence
+    pub before: Vec<String>,
+    /// Divergent line of the left trace, followed by the next lines. Empty if the trace has ended.
+    pub left: Vec<String>,
+    /// Divergent line of the right trace, followed by the next lines. Empty if the trace has ended.
+    pub right: Vec<String>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "First divergence at line {}", self.line)?;
+        for l in &self.before { writeln!(f, "  {}", l)? }
+        if self.left.is_empty() { writeln!(f, "< (end of trace)")? }
+        for l in &self.left { writeln!(f, "< {}", l)? }
+        if self.right.is_empty() { writeln!(f, "> (end of trace)")? }
+        for l in &self.right { writeln!(f, "> {}", l)? }
+        Ok(())
+    }
+}
+
+// Divergent line and up to `n` following lines
+fn next_lines(first: Option<String>, lines: &mut impl Iterator<Item = io::Result<String>>, n: usize) -> io::Result<Vec<String>> {
+    let mut v: Vec<String> = first.into_iter().collect();
+    if !v.is_empty() {
+        for l in lines.take(n) { v.push(l?.trim_end().to_string()) }
+    }
+    Ok(v)
+}
+
+/// Returns the first divergence, with `context` lines before and after it, or None if the traces are identical.
+pub fn diff(left: impl BufRead, right: impl BufRead, context: usize) -> io::Result<Option<Divergence>> {
+    let (mut left, mut right) = (left.lines(), right.lines());
+    let mut before = VecDeque::with_capacity(context + 1);
+    let mut line = 0;
+    loop {
+        line += 1;
+        let l = left.next().transpose()?.map(|l| l.trim_end().to_string());
+        let r = right.next().transpose()?.map(|r| r.trim_end().to_string());
+        match (l, r) {
+            (None, None) => return Ok(None),
+            (Some(l), Some(r)) if l == r => {
+                before.push_back(l);
+                if before.len() > context { before.pop_front(); }
+            },
+            (l, r) => return Ok(Some(Divergence {
+                line,
+                before: before.into(),
+                left: next_lines(l, &mut left, context)?,
+                right: next_lines(r, &mut right, context)?,
+            })),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical() {
+        assert_eq!(diff("a\nb\n".as_bytes(), "a\nb  \n".as_bytes(), 3).unwrap(), None);
+    }
+
+    #[test]
+    fn divergence() {
+        let d = diff("a\nb\nc\nd\ne\n".as_bytes(), "a\nb\nx\ny\n".as_bytes(), 1).unwrap().unwrap();
+        assert_eq!(d, Divergence { line: 3, before: vec!["b".into()], left: vec!["c".into(), "d".into()], right: vec!["x".into(), "y".into()] });
+        assert_eq!(d.to_string(), "First divergence at line 3\n  b\n< c\n< d\n> x\n> y\n");
+    }
+
+    #[test]
+    fn shorter() {
+        let d = diff("a\nb\n".as_bytes(), "a\n".as_bytes(), 2).unwrap().unwrap();
+        assert_eq!((d.line, d.before, d.left, d.right), (2, vec!["a".into()], vec!["b".into()], vec![]));
+    }
+}
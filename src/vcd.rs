@@ -0,0 +1,242 @@
+//! Value Change Dump export of the CPU and bus signals, for waveform viewers (GTKWave, Surfer...).
+//!
+//! VcdWriter is a trace sink : the waveform is built from the bus cycles of the executed instructions.
+//! Each bus cycle starts with SYNC and the status word on the data bus (T1), then the address and data
+//! are valid while DBIN (read) or WR (write, active low) is asserted (T2 - T3). The opcode fetch (M1)
+//! takes 4 clock cycles, other bus cycles 3 clock cycles, and the internal cycles of the instruction
+//! follow its bus cycles. An I/O port number is output on both halves of the address bus.
+//! ```rust
+//! use intel8080::{CPU, vcd::VcdWriter};
+//! use std::{cell::RefCell, rc::Rc};
+//! let mut c = CPU::new();
+//! let vcd = Rc::new(RefCell::new(VcdWriter::new(Vec::new(), c.freq())));
+//! c.set_tracer(vcd.clone());
+//! c.run_cycles(100);
+//! let out = String::from_utf8(vcd.borrow().get_ref().clone()).unwrap();
+//! assert!(out.contains("$var wire 16 ! addr $end"));
+//! ```
+use std::{fs::File, io::{self, BufWriter, Write}, path::Path};
+use crate::{length, step::{Access, AccessKind}, trace::{TraceEvent, TraceSink}};
+
+// Status word bits, output on the data bus during SYNC
+const INTA: u8 = 0x01;
+const WO: u8 = 0x02;
+const STACK: u8 = 0x04;
+const HLTA: u8 = 0x08;
+const OUT: u8 = 0x10;
+const M1: u8 = 0x20;
+const INP: u8 = 0x40;
+const MEMR: u8 = 0x80;
+
+// Name, width and identifier of each signal
+const SIGNALS: [(&str, u8); 18] = [
+    ("addr", 16), ("data", 8), ("sync", 1), ("dbin", 1), ("wr_n", 1), ("status", 8), ("inte", 1), ("hlda", 1),
+    ("pc", 16), ("sp", 16), ("a", 8), ("f", 8), ("b", 8), ("c", 8), ("d", 8), ("e", 8), ("h", 8), ("l", 8),
+];
+const ADDR: usize = 0;
+const DATA: usize = 1;
+const SYNC: usize = 2;
+const DBIN: usize = 3;
+const WR_N: usize = 4;
+const STATUS: usize = 5;
+const INTE: usize = 6;
+const HLDA: usize = 7;
+const PC: usize = 8;
+
+fn id(signal: usize) -> char {
+    char::from(b'!' + signal as u8)
+}
+
+/// Writes a VCD file from the trace events.
+pub struct VcdWriter<W: Write> {
+    out: W,
+    // Clock frequency (MHz)
+    freq: f32,
+    values: [u16; SIGNALS.len()],
+    // Last time written (ns)
+    time: u64,
+    // Bus cycles of the current instruction
+    interrupt: Option<(u16, [u8; 3])>,
+    accesses: Vec<Access>,
+    halt: bool,
+    // End of the last instruction (clock cycles)
+    end: u64,
+}
+
+impl VcdWriter<BufWriter<File>> {
+    /// Creates (or truncates) a VCD file.
+    pub fn file(path: impl AsRef<Path>, freq: f32) -> io::Result<Self> {
+        Ok(VcdWriter::new(BufWriter::new(File::create(path)?), freq))
+    }
+}
+
+impl<W: Write> VcdWriter<W> {
+    /// Writes the VCD header. `freq` is the CPU frequency (MHz), used to convert clock cycles to time.
+    pub fn new(out: W, freq: f32) -> VcdWriter<W> {
+        let mut v = VcdWriter { out, freq, values: [0; SIGNALS.len()], time: 0, interrupt: None, accesses: Vec::new(), halt: false, end: 0 };
+        v.values[WR_N] = 1;
+        let mut header = String::from("$version intel8080 $end\n$timescale 1ns $end\n$scope module i8080 $end\n");
+        for (n, (name, width)) in SIGNALS.iter().enumerate() {
+            header += &format!("$var wire {} {} {} $end\n", width, id(n), name);
+        }
+        header += "$upscope $end\n$enddefinitions $end\n#0\n$dumpvars\n";
+        for n in 0..SIGNALS.len() { header += &v.value(n); }
+        header += "$end\n";
+        v.write(&header);
+        v
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.out
+    }
+
+    /// Returns the writer.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn write(&mut self, s: &str) {
+        // A waveform is a diagnostic output : a write error must not stop the emulation
+        let _ = self.out.write_all(s.as_bytes());
+    }
+
+    fn value(&self, signal: usize) -> String {
+        match SIGNALS[signal].1 {
+            1 => format!("{}{}\n", self.values[signal], id(signal)),
+            _ => format!("b{:b} {}\n", self.values[signal], id(signal)),
+        }
+    }
+
+    // Changes a signal at a clock cycle count
+    fn set(&mut self, cycles: u64, signal: usize, value: u16) {
+        if self.values[signal] == value { return }
+        let time = ((cycles as f64 * 1000.0 / f64::from(self.freq)) as u64).max(self.time);
+        if time != self.time {
+            self.time = time;
+            self.write(&format!("#{}\n", time));
+        }
+        self.values[signal] = value;
+        let v = self.value(signal);
+        self.write(&v);
+    }
+
+    // Bus cycle of `states` clock cycles starting at t : returns its end
+    fn bus_cycle(&mut self, t: u64, states: u64, status: u8, address: u16, data: u8) -> u64 {
+        self.set(t, ADDR, address);
+        self.set(t, STATUS, u16::from(status));
+        self.set(t, DATA, u16::from(status));
+        self.set(t, SYNC, 1);
+        self.set(t + 1, SYNC, 0);
+        self.set(t + 1, DATA, u16::from(data));
+        let strobe = if status & WO == 0 { WR_N } else { DBIN };
+        self.set(t + 1, strobe, if strobe == WR_N { 0 } else { 1 });
+        self.set(t + states, strobe, if strobe == WR_N { 1 } else { 0 });
+        t + states
+    }
+
+    // Lays out the bus cycles of an instruction started at clock
+    fn instruction(&mut self, clock: u64, cycles: u32, pc: u16) {
+        let mut t = clock;
+        if let Some((address, bytes)) = self.interrupt.take() {
+            for (n, b) in bytes.iter().take(usize::from(length(bytes[0]))).enumerate() {
+                let (states, status) = if n == 0 { (4, INTA | WO | M1) } else { (3, INTA | WO) };
+                t = self.bus_cycle(t, states, status, address, *b);
+            }
+        }
+        // The CPU is stalled during DMA transfers (HLDA) : they are not CPU bus cycles
+        let accesses: Vec<Access> = std::mem::take(&mut self.accesses).into_iter().filter(|a| a.kind != AccessKind::DmaWrite).collect();
+        for (n, a) in accesses.iter().enumerate() {
+            let port = a.address << 8 | a.address;
+            t = match a.kind {
+                AccessKind::Fetch if n == 0 => self.bus_cycle(t, 4, MEMR | M1 | WO, a.address, a.value),
+                AccessKind::Fetch | AccessKind::Read => self.bus_cycle(t, 3, MEMR | WO, a.address, a.value),
+                AccessKind::StackRead => self.bus_cycle(t, 3, MEMR | STACK | WO, a.address, a.value),
+                AccessKind::Write => self.bus_cycle(t, 3, 0, a.address, a.value),
+                AccessKind::StackWrite => self.bus_cycle(t, 3, STACK, a.address, a.value),
+                AccessKind::In => self.bus_cycle(t, 3, INP | WO, port, a.value),
+                AccessKind::Out => self.bus_cycle(t, 3, OUT, port, a.value),
+                AccessKind::DmaWrite => t,
+            };
+        }
+        self.end = clock + u64::from(cycles);
+        // HLT : halt acknowledge, the CPU then waits for an interrupt
+        if std::mem::take(&mut self.halt) {
+            self.bus_cycle(t.min(self.end), 1, HLTA | MEMR | WO, pc.wrapping_add(1), 0);
+        }
+    }
+}
+
+impl<W: Write> TraceSink for VcdWriter<W> {
+    fn event(&mut self, event: &TraceEvent) {
+        match *event {
+            TraceEvent::Hold { clock, cycles } => {
+                self.set(clock, HLDA, 1);
+                self.set(clock + u64::from(cycles), HLDA, 0);
+            },
+            TraceEvent::Interrupt { address, bytes } => self.interrupt = Some((address, bytes)),
+            TraceEvent::Bus(a) => self.accesses.push(a),
+            TraceEvent::Halt { .. } => self.halt = true,
+            TraceEvent::Instruction { address, clock, cycles, .. } => self.instruction(clock, cycles, address),
+            TraceEvent::Registers(r) => {
+                let t = self.end;
+                for (n, v) in [r.pc, r.sp, r.a.into(), r.f.into(), r.b.into(), r.c.into(), r.d.into(), r.e.into(), r.h.into(), r.l.into()].iter().enumerate() {
+                    self.set(t, PC + n, *v);
+                }
+                self.set(t, INTE, u16::from(r.inte));
+            },
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{attach, cpu};
+    use std::{cell::RefCell, rc::Rc};
+
+    fn output(vcd: &Rc<RefCell<VcdWriter<Vec<u8>>>>) -> String {
+        String::from_utf8(vcd.borrow().get_ref().clone()).unwrap()
+    }
+
+    #[test]
+    fn header() {
+        let out = String::from_utf8(VcdWriter::new(Vec::new(), 2.0).into_inner()).unwrap();
+        assert!(out.starts_with("$version intel8080 $end\n$timescale 1ns $end\n$scope module i8080 $end\n$var wire 16 ! addr $end\n"));
+        assert!(out.contains("$var wire 1 % wr_n $end\n"));
+        assert!(out.ends_with("$dumpvars\nb0 !\nb0 \"\n0#\n0$\n1%\nb0 &\n0'\n0(\nb0 )\nb0 *\nb0 +\nb0 ,\nb0 -\nb0 .\nb0 /\nb0 0\nb0 1\nb0 2\n$end\n"));
+    }
+
+    #[test]
+    fn bus_cycles() {
+        let mut c = cpu(&[(0x0000, &[0xc5])]);                 // PUSH B
+        c.sp = 0x0100;
+        c.reg.b = 0x12;
+        let vcd = attach(&mut c, VcdWriter::new(Vec::new(), 2.0));
+        c.execute();
+        let out = output(&vcd);
+        let body = &out[out.find("$end\n#").unwrap()..];
+        // M1 : status $A2, 500ns per clock cycle at 2 MHz
+        assert!(body.contains("b10100010 &\nb10100010 \"\n1#\n#500\n0#\nb11000101 \"\n1$\n#2000\n0$\n"));
+        // high byte written first at $00ff : status $04, WR active from T2
+        assert!(body.contains("#2000\n0$\nb11111111 !\nb100 &\nb100 \"\n1#\n#2500\n0#\nb10010 \"\n0%\n#3500\n1%\n"));
+        // registers at the end of the instruction (11 cycles)
+        assert!(body.contains("#5500\nb1 )\nb11111110 *\n"));
+    }
+
+    #[test]
+    fn hold() {
+        struct Dma(bool);
+        impl crate::dma::DmaDevice for Dma {
+            fn hold(&self) -> bool { self.0 }
+            fn transfer(&mut self, _bus: &mut crate::memory::Bus) -> u32 { self.0 = false; 3 }
+        }
+        let mut c = cpu(&[]);
+        c.attach_dma(Dma(true));
+        let vcd = attach(&mut c, VcdWriter::new(Vec::new(), 2.0));
+        c.execute();
+        let out = output(&vcd);
+        // HLDA during 4 cycles, then the NOP fetch
+        assert!(out.contains("\n1(\n#2000\n0(\nb10100010 &\n"));
+    }
+}